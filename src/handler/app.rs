@@ -0,0 +1,565 @@
+//! Module for the app which registers the controllers linked to the routes by their name.
+
+use crate::{
+    handler::middleware::{self, Middleware, MiddlewareFactory, MiddlewareFn, Outcome},
+    http::{extensions::Extensions, request::Request, response::Response},
+    server::tcp_listener,
+    ServerHandle,
+};
+#[cfg(feature = "async")]
+use crate::{
+    handler::{
+        future::{AsyncController, AsyncMiddleware, BoxFuture},
+        middleware::AsyncMiddlewareFactory,
+    },
+    server::async_listener,
+};
+use std::{collections::HashMap, fmt, sync::Arc};
+
+/// The handler of the requests linked to a route.
+/// It's implemented by the functions and closures taking the `Request` and returning the `Response`,
+/// and it can be implemented by any struct which keeps its own state.
+/// # Examples
+/// ```
+/// use kalgan::handler::app::Controller;
+/// use kalgan::http::{request::Request, response::Response};
+///
+/// struct Greeting {
+///     message: String,
+/// }
+/// impl Controller for Greeting {
+///     fn call(&self, _request: &Request) -> Result<Response, String> {
+///         Ok(Response::new().set_status(200).set_content(&self.message))
+///     }
+/// }
+/// ```
+pub trait Controller: Send + Sync {
+    /// Returns the `Response` for the given `Request`.
+    /// # Errors
+    /// Returns the error message rendered by the error controller.
+    fn call(&self, request: &Request) -> Result<Response, String>;
+}
+impl<F> Controller for F
+where
+    F: Fn(&Request) -> Result<Response, String> + Send + Sync,
+{
+    fn call(&self, request: &Request) -> Result<Response, String> {
+        self(request)
+    }
+}
+impl Controller for Box<dyn Controller> {
+    fn call(&self, request: &Request) -> Result<Response, String> {
+        self.as_ref().call(request)
+    }
+}
+/// The registry of the controllers of the app, checked against the routes when the server starts
+/// so a missing controller stops the app at boot instead of failing on every request.
+/// Controller names are written as in the routes file (`user_controller/index` is the same as `user_controller::index`).
+/// # Examples
+/// ```no_run
+/// use kalgan::App;
+/// use kalgan::http::{request::Request, response::Response};
+///
+/// fn hello_world(_request: &Request) -> Result<Response, String> {
+///     Ok(Response::new().set_status(200).set_content("Hello World"))
+/// }
+/// let greeting = "Hello".to_string();
+/// App::new()
+///     .controller("hello_world", hello_world)
+///     .controller("user_controller/index", move |request| {
+///         let name = request.param("name").unwrap_or_default();
+///         Ok(Response::new().set_status(200).set_content(&format!("{} {}", greeting, name)))
+///     })
+///     .run("settings.yaml");
+/// ```
+#[derive(Default)]
+pub struct App {
+    controllers: HashMap<String, Box<dyn Controller>>,
+    middleware: HashMap<String, Box<dyn Middleware>>,
+    middleware_resolver: Option<MiddlewareFn>,
+    #[cfg(feature = "async")]
+    async_middleware_resolver: Option<AsyncMiddleware>,
+    state: Extensions,
+}
+impl App {
+    /// Creates and returns an empty instance of `App`.
+    pub fn new() -> App {
+        App::default()
+    }
+    /// Registers the function or closure as the controller of the given name and returns the instance.
+    pub fn controller<F>(self, name: &str, controller: F) -> Self
+    where
+        F: Fn(&Request) -> Result<Response, String> + Send + Sync + 'static,
+    {
+        self.handler(name, controller)
+    }
+    /// Registers the `Controller` (or boxed `Controller`) as the controller of the given name and returns the instance.
+    /// # Examples
+    /// ```
+    /// use kalgan::App;
+    /// use kalgan::handler::app::Controller;
+    /// use kalgan::http::{request::Request, response::Response};
+    ///
+    /// struct Greeting(String);
+    /// impl Controller for Greeting {
+    ///     fn call(&self, _request: &Request) -> Result<Response, String> {
+    ///         Ok(Response::new().set_status(200).set_content(&self.0))
+    ///     }
+    /// }
+    /// let greeting: Box<dyn Controller> = Box::new(Greeting("Hello".to_string()));
+    /// let app = App::new().handler("hello_world", greeting);
+    /// # assert!(app.has_controller("hello_world"))
+    /// ```
+    pub fn handler<C: Controller + 'static>(mut self, name: &str, controller: C) -> Self {
+        self.controllers
+            .insert(normalize(name), Box::new(controller));
+        self
+    }
+    /// Registers the `Middleware` as the middleware of the given name and returns the instance.
+    /// # Examples
+    /// ```
+    /// use kalgan::App;
+    /// use kalgan::handler::middleware::Middleware;
+    /// use kalgan::http::{request::Request, response::Response};
+    ///
+    /// struct PoweredBy;
+    /// impl Middleware for PoweredBy {
+    ///     fn after(&self, _request: &Request, response: Response) -> Result<Response, String> {
+    ///         Ok(response.set_header("X-Powered-By", "Kalgan"))
+    ///     }
+    /// }
+    /// let app = App::new().middleware("powered_by", PoweredBy);
+    /// # assert!(app.has_middleware("powered_by"))
+    /// ```
+    pub fn middleware<M: Middleware + 'static>(mut self, name: &str, middleware: M) -> Self {
+        self.middleware
+            .insert(name.trim().to_string(), Box::new(middleware));
+        self
+    }
+    /// Sets the middleware resolver of the app, which is called for the middleware not registered, and returns the instance.
+    pub fn set_middleware(mut self, middleware: MiddlewareFn) -> Self {
+        self.middleware_resolver = Some(middleware);
+        self
+    }
+    /// Adds the value to the application state shared by all the requests and returns the instance.
+    /// Controllers and middleware read it with `Request::state`, and a value of the same type replaces the previous one.
+    /// # Examples
+    /// ```
+    /// use kalgan::App;
+    /// use kalgan::http::{request::Request, response::Response};
+    ///
+    /// struct AppState {
+    ///     greeting: String,
+    /// }
+    /// let app = App::new()
+    ///     .set_state(AppState { greeting: "Hello World".to_string() })
+    ///     .controller("hello_world", |request: &Request| {
+    ///         let state = request.state::<AppState>().ok_or("App state not found.")?;
+    ///         Ok(Response::new().set_status(200).set_content(&state.greeting))
+    ///     });
+    /// ```
+    pub fn set_state<T: Send + Sync + 'static>(mut self, state: T) -> Self {
+        self.state.insert(state);
+        self
+    }
+    /// Checks whether a controller has been registered with the given name.
+    pub fn has_controller(&self, name: &str) -> bool {
+        self.controllers.contains_key(&normalize(name))
+    }
+    /// Checks whether a middleware has been registered with the given name.
+    pub fn has_middleware(&self, name: &str) -> bool {
+        self.middleware.contains_key(name.trim())
+    }
+    /// Checks that every controller named in the routes file and in the `error` settings has been registered,
+    /// and so has every middleware of the routes file and the `router.middleware` setting unless there is a middleware resolver.
+    /// # Errors
+    /// Returns the error message with the names of the missing controllers and middleware.
+    /// # Examples
+    /// ```
+    /// use kalgan::App;
+    /// use kalgan::http::{request::Request, response::Response};
+    ///
+    /// # kalgan::mock_settings("tests/mock/settings.yaml");
+    /// # kalgan::mock_routes();
+    /// let app = App::new().controller("hello_world", |_request: &Request| {
+    ///     Ok(Response::new().set_status(200).set_content("Hello World"))
+    /// });
+    /// assert!(app.validate().is_err());
+    /// ```
+    pub fn validate(&self) -> Result<(), String> {
+        let routes = crate::ROUTES.lock().unwrap();
+        let mut names: Vec<String> = routes
+            .collection
+            .iter()
+            .map(|route| route.get_controller().to_string())
+            .collect();
+        std::mem::drop(routes);
+        names.extend(
+            crate::CONFIG
+                .lock()
+                .unwrap()
+                .collection
+                .iter()
+                .filter(|(key, _)| key.starts_with("error."))
+                .filter_map(|(_, value)| value.as_str().map(|name| name.to_string())),
+        );
+        let mut errors = Vec::new();
+        let missing = get_missing(names, |name| self.has_controller(name));
+        if !missing.is_empty() {
+            errors.push(format!(
+                "Controllers not registered in the app: {}.",
+                missing.join(", ")
+            ));
+        }
+        if self.middleware_resolver.is_none() {
+            if let Err(e) = self.validate_middleware() {
+                errors.push(e);
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join(" "))
+        }
+    }
+    /// Checks that every middleware of the routes file and the `router.middleware` setting has been registered.
+    /// # Errors
+    /// Returns the error message with the names of the missing middleware.
+    fn validate_middleware(&self) -> Result<(), String> {
+        let mut middleware: Vec<String> = crate::ROUTES
+            .lock()
+            .unwrap()
+            .collection
+            .iter()
+            .flat_map(|route| middleware::get_stack(route.get_middleware()))
+            .collect();
+        middleware.extend(crate::settings::global_middleware());
+        let missing = get_missing(middleware, |name| self.has_middleware(name));
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Middleware not registered in the app: {}.",
+                missing.join(", ")
+            ))
+        }
+    }
+    /// Parses configuration parameters, routes and translation messages, checks the controllers and finally starts the http server.
+    /// The server is shut down gracefully when a SIGTERM or SIGINT signal is received; a second signal exits immediately.
+    pub fn run(self, settings_file_path: &'static str) {
+        crate::wait_for_signals(self.start(settings_file_path));
+    }
+    /// Parses configuration parameters, routes and translation messages, checks the controllers and starts the http server in a new thread.
+    /// Returns the `ServerHandle` used to shut the server down.
+    /// # Errors
+    /// Returns the error message if a controller is missing or the server cannot be started.
+    pub fn start(self, settings_file_path: &'static str) -> Result<ServerHandle, String> {
+        crate::boot(settings_file_path);
+        self.validate()?;
+        let state = Arc::new(self.state.clone());
+        let app = Arc::new(self);
+        let controller = Arc::clone(&app);
+        tcp_listener::start(
+            settings_file_path,
+            Arc::new(move |request: &Request, name: &str| controller.dispatch(request, name)),
+            Some(app),
+            state,
+        )
+    }
+    #[cfg(feature = "async")]
+    /// Parses configuration parameters, routes and translation messages, checks the middleware and finally starts the async http server
+    /// with the given async resolvers, sharing the state and calling the middleware registered in the app.
+    /// The server is shut down gracefully when a SIGTERM or SIGINT signal is received; a second signal exits immediately.
+    /// # Examples
+    /// ```no_run
+    /// use kalgan::App;
+    /// use kalgan::handler::future::BoxFuture;
+    /// use kalgan::http::{request::Request, response::Response};
+    ///
+    /// struct AppState {
+    ///     greeting: String,
+    /// }
+    /// fn resolver<'a>(request: &'a Request, _controller: &'a str) -> BoxFuture<'a, Result<Response, String>> {
+    ///     Box::pin(async move {
+    ///         let state = request.state::<AppState>().ok_or("App state not found.")?;
+    ///         Ok(Response::new().set_status(200).set_content(&state.greeting))
+    ///     })
+    /// }
+    /// App::new()
+    ///     .set_state(AppState { greeting: "Hello World".to_string() })
+    ///     .run_async("settings.yaml", resolver, None);
+    /// ```
+    pub fn run_async(
+        self,
+        settings_file_path: &'static str,
+        controller: AsyncController,
+        middleware: Option<AsyncMiddleware>,
+    ) {
+        crate::wait_for_signals(self.start_async(settings_file_path, controller, middleware));
+    }
+    #[cfg(feature = "async")]
+    /// Parses configuration parameters, routes and translation messages, checks the middleware and starts the async http server in a new thread
+    /// with the given async resolvers, sharing the state and calling the middleware registered in the app.
+    /// The async middleware resolver is called for the middleware not registered; without it, every middleware
+    /// of the routes file and the `router.middleware` setting must be registered.
+    /// Returns the `ServerHandle` used to shut the server down.
+    /// # Errors
+    /// Returns the error message if a middleware is missing or the server cannot be started.
+    pub fn start_async(
+        mut self,
+        settings_file_path: &'static str,
+        controller: AsyncController,
+        middleware: Option<AsyncMiddleware>,
+    ) -> Result<ServerHandle, String> {
+        crate::boot(settings_file_path);
+        self.async_middleware_resolver = middleware;
+        if self.async_middleware_resolver.is_none() {
+            self.validate_middleware()?;
+        }
+        let state = Arc::new(self.state.clone());
+        async_listener::start(settings_file_path, controller, Some(Arc::new(self)), state)
+    }
+    /// Calls the controller of the given name.
+    fn dispatch(&self, request: &Request, name: &str) -> Result<Response, String> {
+        match self.controllers.get(&normalize(name)) {
+            Some(controller) => controller.call(request),
+            None => Err(format!("Controller {} not registered in the app.", name)),
+        }
+    }
+}
+impl MiddlewareFactory for App {
+    fn before(&self, request: &mut Request, middleware: &str) -> Result<Outcome, String> {
+        match (self.middleware.get(middleware), self.middleware_resolver) {
+            (Some(handler), _) => handler.before(request),
+            (None, Some(resolver)) => resolver(request, middleware),
+            (None, None) => Err(format!(
+                "Middleware {} not registered in the app.",
+                middleware
+            )),
+        }
+    }
+    fn after(
+        &self,
+        request: &Request,
+        middleware: &str,
+        response: Response,
+    ) -> Result<Response, String> {
+        match self.middleware.get(middleware) {
+            Some(handler) => handler.after(request, response),
+            None => Ok(response),
+        }
+    }
+}
+#[cfg(feature = "async")]
+impl AsyncMiddlewareFactory for App {
+    fn before<'a>(
+        &'a self,
+        request: &'a mut Request,
+        middleware: &'a str,
+    ) -> BoxFuture<'a, Result<Outcome, String>> {
+        match (
+            self.middleware.get(middleware),
+            self.async_middleware_resolver,
+        ) {
+            (Some(handler), _) => Box::pin(async move { handler.before(request) }),
+            (None, Some(resolver)) => resolver(request, middleware),
+            (None, None) => Box::pin(async move {
+                Err(format!(
+                    "Middleware {} not registered in the app.",
+                    middleware
+                ))
+            }),
+        }
+    }
+    fn after<'a>(
+        &'a self,
+        request: &'a Request,
+        middleware: &'a str,
+        response: Response,
+    ) -> BoxFuture<'a, Result<Response, String>> {
+        Box::pin(async move {
+            match self.middleware.get(middleware) {
+                Some(handler) => handler.after(request, response),
+                None => Ok(response),
+            }
+        })
+    }
+}
+impl fmt::Debug for App {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut names: Vec<&String> = self.controllers.keys().collect();
+        names.sort();
+        let mut middleware: Vec<&String> = self.middleware.keys().collect();
+        middleware.sort();
+        f.debug_struct("App")
+            .field("controllers", &names)
+            .field("middleware", &middleware)
+            .field("middleware_resolver", &self.middleware_resolver.is_some())
+            .field("state", &self.state)
+            .finish()
+    }
+}
+/// Returns the sorted names without duplicates which are not registered.
+fn get_missing<F: Fn(&str) -> bool>(names: Vec<String>, is_registered: F) -> Vec<String> {
+    let mut missing: Vec<String> = names
+        .into_iter()
+        .filter(|name| !is_registered(name))
+        .collect();
+    missing.sort();
+    missing.dedup();
+    missing
+}
+/// Returns the controller name as it's written by the router (`user_controller/index` becomes `user_controller::index`).
+fn normalize(name: &str) -> String {
+    name.replace('/', "::")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+    };
+
+    struct Name(String);
+    struct PoweredBy;
+    impl Middleware for PoweredBy {
+        fn after(&self, _request: &Request, response: Response) -> Result<Response, String> {
+            Ok(response.set_header("X-Powered-By", "Kalgan"))
+        }
+    }
+    struct Guard;
+    impl Middleware for Guard {
+        fn before(&self, request: &mut Request) -> Result<Outcome, String> {
+            Ok(Outcome {
+                success: request.get_header("X-Token").is_some(),
+                response: Some(Response::new().set_status(401)),
+            })
+        }
+    }
+
+    fn get_app() -> App {
+        let greeting = "Hello".to_string();
+        [
+            "user_controller/index",
+            "export",
+            "user_create",
+            "upload",
+            "post_show",
+            "client",
+        ]
+        .iter()
+        .fold(App::new(), |app, name| {
+            app.controller(name, |_request| Err("Not implemented.".to_string()))
+        })
+        .controller("hello_world", move |request| {
+            let name = request.state::<Name>().ok_or("App state not found.")?;
+            Ok(Response::new()
+                .set_status(200)
+                .set_content(&format!("{} {}", greeting, name.0)))
+        })
+        .set_state(Name("World".to_string()))
+        .middleware("powered_by", PoweredBy)
+        .middleware("guard", Guard)
+    }
+    fn send(server: &ServerHandle, request: &[u8]) -> String {
+        let mut client = TcpStream::connect(server.local_addr()).unwrap();
+        client.write_all(request).unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_validate() {
+        crate::tests::set_config();
+        crate::mock_routes();
+        assert!(get_app().validate().is_ok());
+        let app =
+            App::new().controller(
+                "hello_world",
+                |_request| Ok(Response::new().set_status(200)),
+            );
+        let e = app.validate().unwrap_err();
+        assert!(e.contains("user_controller::index"));
+        assert!(!e.contains("hello_world"));
+        assert!(e.ends_with("Middleware not registered in the app: guard, powered_by."));
+    }
+    #[test]
+    fn test_start_dispatches_to_registered_controller_with_state() {
+        let server = get_app().start("tests/mock/settings.yaml").unwrap();
+        let response = send(
+            &server,
+            b"GET /hello-world HTTP/1.1\r\nConnection: close\r\n\r\n",
+        );
+        server.shutdown();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("\r\n\r\nHello World"));
+        assert!(!response.contains("X-Powered-By"));
+    }
+    #[test]
+    fn test_start_calls_middleware_stack() {
+        let server = get_app().start("tests/mock/settings.yaml").unwrap();
+        let response = send(
+            &server,
+            b"GET /secure HTTP/1.1\r\nConnection: close\r\n\r\n",
+        );
+        assert!(response.starts_with("HTTP/1.1 401 Unauthorized"));
+        assert!(response.contains("X-Powered-By: Kalgan\r\n"));
+        let response = send(
+            &server,
+            b"GET /secure HTTP/1.1\r\nX-Token: 1\r\nConnection: close\r\n\r\n",
+        );
+        server.shutdown();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("X-Powered-By: Kalgan\r\n"));
+        assert!(response.ends_with("\r\n\r\nHello World"));
+    }
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_start_async_calls_middleware_stack() {
+        fn controller<'a>(
+            request: &'a Request,
+            _controller: &'a str,
+        ) -> BoxFuture<'a, Result<Response, String>> {
+            Box::pin(async move {
+                let name = request.state::<Name>().ok_or("App state not found.")?;
+                Ok(Response::new().set_status(200).set_content(&name.0))
+            })
+        }
+        let server = get_app()
+            .start_async("tests/mock/settings.yaml", controller, None)
+            .unwrap();
+        let response = send(
+            &server,
+            b"GET /secure HTTP/1.1\r\nConnection: close\r\n\r\n",
+        );
+        assert!(response.starts_with("HTTP/1.1 401 Unauthorized"));
+        assert!(response.contains("X-Powered-By: Kalgan\r\n"));
+        let response = send(
+            &server,
+            b"GET /secure HTTP/1.1\r\nX-Token: 1\r\nConnection: close\r\n\r\n",
+        );
+        server.shutdown();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("X-Powered-By: Kalgan\r\n"));
+        assert!(response.ends_with("\r\n\r\nWorld"));
+    }
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_start_async_checks_middleware() {
+        fn controller<'a>(
+            _request: &'a Request,
+            _controller: &'a str,
+        ) -> BoxFuture<'a, Result<Response, String>> {
+            Box::pin(async move { Ok(Response::new().set_status(200)) })
+        }
+        let e = App::new()
+            .middleware("guard", Guard)
+            .start_async("tests/mock/settings.yaml", controller, None)
+            .err()
+            .unwrap();
+        assert_eq!(e, "Middleware not registered in the app: powered_by.");
+    }
+}
@@ -13,9 +13,9 @@ pub fn is_static_file(request: &Request) -> bool {
         Ok(static_folders) => {
             for static_folder in static_folders.trim().split(",") {
                 let clean_static_folder = kalgan_string::strip(&static_folder.trim(), '/');
-                if request.get_uri().contains(&clean_static_folder)
+                if request.get_path().contains(&clean_static_folder)
                     && request
-                        .get_uri()
+                        .get_path()
                         .find(format!("/{}/", &clean_static_folder).as_str())
                         == Some(0)
                 {
@@ -27,29 +27,29 @@ pub fn is_static_file(request: &Request) -> bool {
         Err(_e) => false,
     }
 }
-/// Returns the content of the static file.
-pub fn serve_static(request: &Request) -> Vec<u8> {
+/// Returns the `Response` streaming the content of the static file.
+pub fn serve_static(request: &Request, keep_alive: bool) -> Option<Response> {
     info!("Processing static file...");
-    let uri = get_clean_static_file(&request.get_uri());
-    match fs::read(&uri) {
-        Ok(mut contents) => {
-            let result = Response::new()
+    let uri = get_clean_static_file(&request.get_path());
+    match fs::File::open(&uri).and_then(|file| Ok((file.metadata()?.len(), file))) {
+        Ok((length, file)) => Some(
+            Response::new()
                 .set_status(200)
                 .set_content_type(get_content_type(&uri))
-                .create();
-            let mut bytes = result;
-            bytes.append(&mut contents);
-            bytes
-        }
+                .set_content_length(length as usize)
+                .set_stream(file)
+                .set_head(request.get_method().eq_ignore_ascii_case("HEAD"))
+                .set_connection(keep_alive, settings::keep_alive_timeout()),
+        ),
         Err(e) => {
             warn!("Error processing static file \"{}\".", &uri);
             warn!("{}", e);
-            Vec::new()
+            None
         }
     }
 }
-/// Returns the content type of the static file.
-fn get_content_type(uri: &str) -> &str {
+/// Returns the content type of the file for the given path.
+pub(crate) fn get_content_type(uri: &str) -> &str {
     let chunks: Vec<&str> = uri.split(".").collect();
     match chunks[chunks.len() - 1] {
         "aac" => "audio/aac",
@@ -83,13 +83,11 @@ fn get_content_type(uri: &str) -> &str {
         "xls" => "application/vnd.ms-excel",
         "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
         "xml" => "application/xml",
+        "zip" => "application/zip",
         _ => "application/octet-stream",
     }
 }
 /// Returns the path of the static file.
-fn get_clean_static_file(uri: &str) -> &str {
-    match uri.find("?") {
-        Some(pos) => kalgan_string::strip_left(&uri[..pos], '/'),
-        None => kalgan_string::strip_left(&uri, '/'),
-    }
+fn get_clean_static_file(path: &str) -> &str {
+    kalgan_string::strip_left(path, '/')
 }
@@ -1,38 +1,40 @@
 //! Module for the controller which receives the request object and returns a response object.
 
+#[cfg(feature = "async")]
+use crate::handler::{future::AsyncController, middleware::AsyncMiddlewareFactory};
 use crate::{
-    handler::{error, middleware, middleware::Outcome},
+    handler::{error, middleware, middleware::MiddlewareFactory, routes},
     http::{request::Request, response::Response},
     settings,
 };
 use log::info;
 use std::str::FromStr;
 
+/// The function which calls the controller of the given name, either the resolver of the app or its `App` registry.
+pub(crate) type ControllerFactory =
+    dyn Fn(&Request, &str) -> Result<Response, String> + Send + Sync;
+
+/// The reasons why no route is linked to the `Request`.
+enum Mismatch {
+    /// No route matches the path (404 Not Found).
+    Path(String),
+    /// Routes match the path but not the method (405 Method Not Allowed), along with the allowed methods.
+    Method(String, Vec<String>),
+    /// An OPTIONS request for a path without OPTIONS route, along with the allowed methods.
+    Options(Vec<String>),
+}
 /// Passes the `Request` to the middleware/controller linked to the route and returns the `Response` of the middleware/controller.
 pub fn resolver(
     request: &mut Request,
-    controller_factory: &fn(&Request, &str) -> Result<Response, String>,
-    middleware_factory: Option<&fn(&mut Request, &str) -> Result<Outcome, String>>,
+    controller_factory: &ControllerFactory,
+    middleware_factory: Option<&dyn MiddlewareFactory>,
 ) -> Response {
-    let routes = crate::ROUTES.lock().unwrap();
-    match routes.get_route(&request.get_uri(), &request.get_method()) {
-        Ok(route) => {
-            info!("Route matched:");
-            info!("{:#?}", &route);
-            request.route = Some(route.clone());
-            if request.route.as_ref().unwrap().language.is_empty()
-                && settings::exists("i18n.language.default")
-            {
-                request.route.as_mut().unwrap().language =
-                    settings::get_string("i18n.language.default").unwrap();
-            }
-            let controller = String::from_str(&route.get_controller()).unwrap();
-            let middleware = String::from_str(&route.get_middleware()).unwrap();
-            std::mem::drop(routes);
+    match match_route(request) {
+        Ok((controller, middleware)) => {
             if middleware.is_empty() {
                 info!("No middleware has been defined for this route.");
                 info!("Calling controller {}...", &controller);
-                match controller_factory(&request, &controller) {
+                match controller_factory(request, &controller) {
                     Ok(response) => response,
                     Err(e) => error::render(request, 500, &e, controller_factory),
                 }
@@ -46,9 +48,127 @@ pub fn resolver(
                 )
             }
         }
-        Err(e) => {
-            std::mem::drop(routes);
-            error::render(request, 404, &e, controller_factory)
+        Err(Mismatch::Path(e)) => error::render(request, 404, &e, controller_factory),
+        Err(Mismatch::Method(e, methods)) => error::render(request, 405, &e, controller_factory)
+            .set_header("Allow", &get_allow_header(methods)),
+        Err(Mismatch::Options(methods)) => get_options_response(methods),
+    }
+}
+#[cfg(feature = "async")]
+/// Passes the `Request` to the async middleware/controller linked to the route and returns the `Response` of the middleware/controller.
+pub(crate) async fn resolver_async(
+    request: &mut Request,
+    controller_factory: AsyncController,
+    middleware_factory: Option<&dyn AsyncMiddlewareFactory>,
+) -> Response {
+    match match_route(request) {
+        Ok((controller, middleware)) => {
+            if middleware.is_empty() {
+                info!("No middleware has been defined for this route.");
+                info!("Calling controller {}...", &controller);
+                match controller_factory(request, &controller).await {
+                    Ok(response) => response,
+                    Err(e) => error::render_async(request, 500, &e, controller_factory).await,
+                }
+            } else {
+                middleware::resolver_async(
+                    request,
+                    controller_factory,
+                    middleware_factory,
+                    &controller,
+                    &middleware,
+                )
+                .await
+            }
+        }
+        Err(Mismatch::Path(e)) => error::render_async(request, 404, &e, controller_factory).await,
+        Err(Mismatch::Method(e, methods)) => {
+            error::render_async(request, 405, &e, controller_factory)
+                .await
+                .set_header("Allow", &get_allow_header(methods))
+        }
+        Err(Mismatch::Options(methods)) => get_options_response(methods),
+    }
+}
+/// Links the route matching the `Request` to it and returns the names of its controller and middleware stack.
+/// HEAD requests are linked to the GET route when no HEAD route is defined.
+/// OPTIONS requests are answered with the methods allowed for the path when no OPTIONS route is defined.
+/// # Errors
+/// Returns the reason why no route matches the `Request`.
+fn match_route(request: &mut Request) -> Result<(String, Vec<String>), Mismatch> {
+    let host = request.client_host();
+    let routes = crate::ROUTES.lock().unwrap();
+    let patterns = crate::ROUTE_PATTERNS.lock().unwrap();
+    let route = routes::find(
+        &routes,
+        &patterns,
+        request.get_path(),
+        request.get_method(),
+        &host,
+    )
+    .or_else(|e| {
+        if request.get_method().eq_ignore_ascii_case("HEAD") {
+            routes::find(&routes, &patterns, request.get_path(), "GET", &host)
+        } else {
+            Err(e)
         }
+    })
+    .map_err(|e| {
+        let methods = routes::get_allowed_methods(&routes, &patterns, request.get_path(), &host);
+        if methods.is_empty() {
+            Mismatch::Path(e)
+        } else if request.get_method().eq_ignore_ascii_case("OPTIONS") {
+            Mismatch::Options(methods)
+        } else {
+            Mismatch::Method(e, methods)
+        }
+    })?;
+    std::mem::drop(patterns);
+    std::mem::drop(routes);
+    info!("Route matched:");
+    info!("{:#?}", &route);
+    let controller = String::from_str(route.get_controller()).unwrap();
+    let middleware = middleware::get_stack(route.get_middleware());
+    request.route = Some(route);
+    if request.route.as_ref().unwrap().language.is_empty()
+        && settings::exists("i18n.language.default")
+    {
+        request.route.as_mut().unwrap().language =
+            settings::get_string("i18n.language.default").unwrap();
+    }
+    Ok((controller, middleware))
+}
+/// Returns the `Response` of an OPTIONS request listing the allowed methods in the `Allow` header.
+fn get_options_response(methods: Vec<String>) -> Response {
+    Response::new()
+        .set_status(204)
+        .set_header("Allow", &get_allow_header(methods))
+}
+/// Returns the value of the `Allow` header for the methods of the routes,
+/// adding the ones answered automatically (HEAD for GET routes and OPTIONS).
+fn get_allow_header(mut methods: Vec<String>) -> String {
+    if methods.iter().any(|method| method == "GET") {
+        methods.push("HEAD".to_string());
+    }
+    methods.push("OPTIONS".to_string());
+    methods.sort();
+    methods.dedup();
+    methods.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_allow_header() {
+        assert_eq!(
+            get_allow_header(vec!["GET".to_string(), "PUT".to_string()]),
+            "GET, HEAD, OPTIONS, PUT"
+        );
+        assert_eq!(
+            get_allow_header(vec!["OPTIONS".to_string(), "POST".to_string()]),
+            "OPTIONS, POST"
+        );
     }
 }
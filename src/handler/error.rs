@@ -1,21 +1,26 @@
 //! Module for the error handler.
 
+#[cfg(feature = "async")]
+use crate::handler::future::AsyncController;
 #[cfg(feature = "tera")]
 use crate::template::{self, Context, Sugar};
 use crate::{
-    http::{request::Request, response::Response},
+    handler::controller::ControllerFactory,
+    http::{request::Request, response::Response, status::StatusCode},
     settings,
 };
 use log::{error, warn};
+use std::convert::TryFrom;
 
 /// Returns the `Response` of the error controller (if exists).
 pub fn render(
     request: &mut Request,
     error_code: i32,
     message: &str,
-    controller_factory: &fn(&Request, &str) -> Result<Response, String>,
+    controller_factory: &ControllerFactory,
 ) -> Response {
     error!("{}", message);
+    let error_code = get_error_code(request, error_code, message);
     let controller_key = format!("error.{}", error_code);
     match &settings::get_string(&controller_key) {
         Ok(controller) => match controller_factory(&request, &controller) {
@@ -28,15 +33,68 @@ pub fn render(
         }
     }
 }
+#[cfg(feature = "async")]
+/// Returns the `Response` of the async error controller (if exists).
+pub(crate) async fn render_async(
+    request: &mut Request,
+    error_code: i32,
+    message: &str,
+    controller_factory: AsyncController,
+) -> Response {
+    error!("{}", message);
+    let error_code = get_error_code(request, error_code, message);
+    let controller_key = format!("error.{}", error_code);
+    match &settings::get_string(&controller_key) {
+        Ok(controller) => match controller_factory(request, controller).await {
+            Ok(response) => response,
+            Err(e) => trigger_internal_error(request, error_code, &e),
+        },
+        Err(e) => {
+            warn!("{}", e);
+            trigger_internal_error(request, error_code, message)
+        }
+    }
+}
+/// Returns the `Response` for an error raised before the `Request` object could be created.
+pub(crate) fn render_without_request(error_code: i32, message: &str) -> Response {
+    error!("{}", message);
+    let error_message = get_error_message(error_code);
+    let contents = if settings::is_prod() {
+        format!(
+            " An error occurred :( <br> HTTP/1.1 {} {}.",
+            error_code, error_message
+        )
+    } else {
+        format!(
+            "HTTP/1.1 {} {}<br><br>{}.",
+            error_code, error_message, message
+        )
+    };
+    Response::new()
+        .set_status(error_code)
+        .set_content_type("text/html; charset=UTF-8")
+        .set_content(&contents)
+}
+/// Returns the status code of the extraction error raised while the controller or middleware was handling the request
+/// if it failed with that error (such as when it's propagated with `?`), otherwise the given error code.
+fn get_error_code(request: &Request, error_code: i32, message: &str) -> i32 {
+    match request.take_rejection() {
+        Some(rejection) if error_code == 500 && rejection.to_string() == message => {
+            rejection.get_status()
+        }
+        _ => error_code,
+    }
+}
+/// Returns the reason phrase of the given error code.
+fn get_error_message(error_code: i32) -> &'static str {
+    match StatusCode::try_from(error_code) {
+        Ok(status) => status.reason(),
+        Err(_) => "",
+    }
+}
 /// Returns the `Response` of the internal error template.
 fn trigger_internal_error(request: &Request, error_code: i32, message: &str) -> Response {
-    let error_message = match error_code {
-        403 => "Forbidden",
-        404 => "Not Found",
-        500 => "Internal Server Error",
-        503 => "Service Unavailable",
-        _ => "",
-    };
+    let error_message = get_error_message(error_code);
     let contents = if settings::is_prod() {
         format!(
             " An error occurred :( <br> HTTP/1.1 {} {}.",
@@ -61,13 +119,47 @@ fn trigger_internal_error(request: &Request, error_code: i32, message: &str) ->
                     .add("error_code", &error_code.to_string())
                     .add("error_message", error_message)
                     .add("message", message)
-                    .add("request", request),
+                    .add("request", request)
+                    .add("raw", &request.get_raw()),
             )
         }
     };
     Response::new()
         .set_status(error_code)
         .set_content_type("text/html; charset=UTF-8")
-        .set_content_length(contents.len())
         .set_content(&contents)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_get_error_code() {
+        let request = Request::new(
+            b"POST / HTTP/1.1\r\nContent-Type: application/json\r\n\r\n{\"age\": \"many\"}",
+        )
+        .unwrap();
+        let e: String = request.json::<HashMap<String, u32>>().unwrap_err().into();
+        assert_eq!(get_error_code(&request, 500, &e), 422);
+        assert_eq!(get_error_code(&request, 500, &e), 500);
+        let e: String = request.json::<HashMap<String, u32>>().unwrap_err().into();
+        assert_eq!(get_error_code(&request, 404, &e), 404);
+        let _ = request.json::<HashMap<String, u32>>();
+        assert_eq!(
+            get_error_code(&request, 500, "The user cannot be created."),
+            500
+        );
+    }
+    #[test]
+    fn test_get_error_code_after_handled_rejection() {
+        let request = Request::new(b"GET /users?page=first HTTP/1.1\r\n\r\n").unwrap();
+        let page = request
+            .query::<HashMap<String, u32>>()
+            .map(|query| query["page"])
+            .unwrap_or(1);
+        assert_eq!(page, 1);
+        assert_eq!(get_error_code(&request, 500, "Database unavailable."), 500);
+    }
+}
@@ -0,0 +1,49 @@
+//! Module for the types of the async controllers and middlewares.
+//!
+//! Async handlers are declared as plain functions that return a boxed future:
+//! ```
+//! use kalgan::handler::future::BoxFuture;
+//! use kalgan::http::{request::Request, response::Response};
+//! # use kalgan::handler::future::AsyncController;
+//!
+//! async fn hello_world(_request: &Request) -> Result<Response, String> {
+//!     Ok(Response::new().set_status(200).set_content("Hello World"))
+//! }
+//! pub fn resolver<'a>(request: &'a Request, controller: &'a str) -> BoxFuture<'a, Result<Response, String>> {
+//!     Box::pin(async move {
+//!         match controller {
+//!             "hello_world" => hello_world(request).await,
+//!             _ => Err(format!("Controller {} not found in resolver.", &controller)),
+//!         }
+//!     })
+//! }
+//! # let controller: AsyncController = resolver;
+//! ```
+//! Async middlewares follow the same pattern:
+//! ```
+//! use kalgan::handler::{future::BoxFuture, middleware::Outcome};
+//! use kalgan::http::request::Request;
+//!
+//! pub fn resolver<'a>(request: &'a mut Request, middleware: &'a str) -> BoxFuture<'a, Result<Outcome, String>> {
+//!     Box::pin(async move {
+//!         request.middleware.insert("name".to_string(), middleware.to_string());
+//!         Ok(Outcome { success: true, response: None })
+//!     })
+//! }
+//! # let middleware: kalgan::handler::future::AsyncMiddleware = resolver;
+//! ```
+
+use crate::{
+    handler::middleware::Outcome,
+    http::{request::Request, response::Response},
+};
+use std::{future::Future, pin::Pin};
+
+/// A boxed future that can be sent between threads.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+/// The signature of the async controller factory passed to `kalgan::run_async`.
+pub type AsyncController =
+    for<'a> fn(&'a Request, &'a str) -> BoxFuture<'a, Result<Response, String>>;
+/// The signature of the async middleware factory passed to `kalgan::run_async`.
+pub type AsyncMiddleware =
+    for<'a> fn(&'a mut Request, &'a str) -> BoxFuture<'a, Result<Outcome, String>>;
@@ -1,49 +1,307 @@
 //! Module for the middleware which receives the request object and returns the outcome object.
+//!
+//! The middleware of a route are called in order: first the ones of the `router.middleware` setting (global)
+//! and then the ones of the route, written as a comma separated list (`middleware: auth, csrf`).
+//! Each middleware can stop the request before the controller and process the `Response` afterwards.
 
+#[cfg(feature = "async")]
+use crate::handler::future::{AsyncController, AsyncMiddleware, BoxFuture};
 use crate::{
-    handler::error,
+    handler::{controller::ControllerFactory, error},
     http::{request::Request, response::Response},
+    settings,
 };
 use log::info;
 
+/// The signature of the middleware resolver passed to `kalgan::run`.
+pub type MiddlewareFn = fn(&mut Request, &str) -> Result<Outcome, String>;
+
 #[derive(Debug)]
 /// The object returned by the middleware.
-/// * If `Outcome.success` is set to `true` the `Request` is passed to the controller.
+/// * If `Outcome.success` is set to `true` the `Request` is passed to the next middleware or the controller.
 /// * If `Outcome.success` is set to `false` the controller is skipped and the `Outcome.response` is sent to the browser.
 pub struct Outcome {
     pub success: bool,
     pub response: Option<Response>,
 }
-/// Passes the `Request` to the middleware/controller linked to the route and returns the `Response` of the middleware/controller.
+/// A middleware with a hook called before the controller and a hook called after it.
+/// Both hooks do nothing by default.
+/// # Examples
+/// ```
+/// use kalgan::handler::middleware::{Middleware, Outcome};
+/// use kalgan::http::{request::Request, response::Response};
+/// use std::time::Instant;
+///
+/// struct Timing;
+/// impl Middleware for Timing {
+///     fn before(&self, request: &mut Request) -> Result<Outcome, String> {
+///         request.extensions_mut().insert(Instant::now());
+///         Ok(Outcome { success: true, response: None })
+///     }
+///     fn after(&self, request: &Request, response: Response) -> Result<Response, String> {
+///         let elapsed = request.extensions().get::<Instant>().map(|start| start.elapsed().as_millis());
+///         Ok(response.set_header("X-Response-Time", &format!("{}ms", elapsed.unwrap_or_default())))
+///     }
+/// }
+/// ```
+pub trait Middleware: Send + Sync {
+    /// Called before the controller, it can stop the request returning an unsuccessful `Outcome`.
+    /// # Errors
+    /// Returns the error message rendered by the error controller.
+    fn before(&self, _request: &mut Request) -> Result<Outcome, String> {
+        Ok(Outcome {
+            success: true,
+            response: None,
+        })
+    }
+    /// Called after the controller (or the next middleware) with its `Response`, which can be modified or replaced.
+    /// It's not called if the `before` hook stopped the request.
+    /// # Errors
+    /// Returns the error message rendered by the error controller.
+    fn after(&self, _request: &Request, response: Response) -> Result<Response, String> {
+        Ok(response)
+    }
+}
+impl Middleware for Box<dyn Middleware> {
+    fn before(&self, request: &mut Request) -> Result<Outcome, String> {
+        self.as_ref().before(request)
+    }
+    fn after(&self, request: &Request, response: Response) -> Result<Response, String> {
+        self.as_ref().after(request, response)
+    }
+}
+/// The object which calls the hooks of the middleware of the given name,
+/// either the middleware resolver of the app or its `App` registry.
+pub(crate) trait MiddlewareFactory: Send + Sync {
+    /// Calls the hook of the given middleware before the controller.
+    fn before(&self, request: &mut Request, middleware: &str) -> Result<Outcome, String>;
+    /// Calls the hook of the given middleware after the controller.
+    fn after(
+        &self,
+        request: &Request,
+        middleware: &str,
+        response: Response,
+    ) -> Result<Response, String>;
+}
+impl MiddlewareFactory for MiddlewareFn {
+    fn before(&self, request: &mut Request, middleware: &str) -> Result<Outcome, String> {
+        self(request, middleware)
+    }
+    fn after(
+        &self,
+        _request: &Request,
+        _middleware: &str,
+        response: Response,
+    ) -> Result<Response, String> {
+        Ok(response)
+    }
+}
+#[cfg(feature = "async")]
+/// The object which calls the hooks of the middleware of the given name in the async server,
+/// either the async middleware resolver of the app or its `App` registry.
+pub(crate) trait AsyncMiddlewareFactory: Send + Sync {
+    /// Calls the hook of the given middleware before the controller.
+    fn before<'a>(
+        &'a self,
+        request: &'a mut Request,
+        middleware: &'a str,
+    ) -> BoxFuture<'a, Result<Outcome, String>>;
+    /// Calls the hook of the given middleware after the controller.
+    fn after<'a>(
+        &'a self,
+        request: &'a Request,
+        middleware: &'a str,
+        response: Response,
+    ) -> BoxFuture<'a, Result<Response, String>>;
+}
+#[cfg(feature = "async")]
+impl AsyncMiddlewareFactory for AsyncMiddleware {
+    fn before<'a>(
+        &'a self,
+        request: &'a mut Request,
+        middleware: &'a str,
+    ) -> BoxFuture<'a, Result<Outcome, String>> {
+        self(request, middleware)
+    }
+    fn after<'a>(
+        &'a self,
+        _request: &'a Request,
+        _middleware: &'a str,
+        response: Response,
+    ) -> BoxFuture<'a, Result<Response, String>> {
+        Box::pin(async move { Ok(response) })
+    }
+}
+/// Returns the names of the middleware to be called for the given middleware of the route:
+/// the ones of the `router.middleware` setting followed by the ones of the route.
+pub(crate) fn get_stack(route_middleware: &str) -> Vec<String> {
+    let mut stack = settings::global_middleware();
+    stack.extend(
+        route_middleware
+            .split(',')
+            .map(|middleware| middleware.trim())
+            .filter(|middleware| !middleware.is_empty())
+            .map(|middleware| middleware.to_string()),
+    );
+    stack
+}
+/// Passes the `Request` to the middleware stack and the controller linked to the route
+/// and returns the `Response` processed by the middleware in reverse order.
 pub(crate) fn resolver(
     request: &mut Request,
-    controller_factory: &fn(&Request, &str) -> Result<Response, String>,
-    middleware_factory: Option<&fn(&mut Request, &str) -> Result<Outcome, String>>,
+    controller_factory: &ControllerFactory,
+    middleware_factory: Option<&dyn MiddlewareFactory>,
     controller: &str,
-    middleware: &str,
+    middleware: &[String],
 ) -> Response {
-    match middleware_factory {
-        Some(factory) => {
-            info!("Calling middleware {}...", &middleware);
-            match factory(request, &middleware) {
-                Ok(outcome) => {
-                    if outcome.success {
-                        info!("Middleware response is successful.");
-                        info!("Calling controller {}...", &controller);
-                        match controller_factory(&request, &controller) {
-                            Ok(response) => response,
-                            Err(e) => error::render(request, 500, &e, controller_factory)
-                        }
-                    } else {
-                        info!("Middleware response is not successful.");
-                        info!("Controller {} is skipped.", &controller);
-                        match outcome.response {
-                            Some(response) => response,
-                            None => error::render(request, 500, "No Middleware response was set.", controller_factory)
-                        }
-                    }
-                }, Err(e) => error::render(request, 500, &e, controller_factory)
+    let factory = match middleware_factory {
+        Some(factory) => factory,
+        None => {
+            let message = get_missing_factory_message(controller, middleware);
+            return error::render(request, 500, &message, controller_factory);
+        }
+    };
+    let mut called = 0;
+    let mut stopped = None;
+    for name in middleware {
+        info!("Calling middleware {}...", &name);
+        match get_stop(factory.before(request, name), controller) {
+            Ok(None) => called += 1,
+            Ok(Some(response)) => {
+                stopped = Some(response);
+                break;
+            }
+            Err(e) => {
+                stopped = Some(error::render(request, 500, &e, controller_factory));
+                break;
+            }
+        }
+    }
+    let mut response = match stopped {
+        Some(response) => response,
+        None => {
+            info!("Calling controller {}...", &controller);
+            match controller_factory(request, controller) {
+                Ok(response) => response,
+                Err(e) => error::render(request, 500, &e, controller_factory),
             }
-        }, None => error::render(request, 500, &format!("Middleware \"{}\" is set for Controller \"{}\" but Middleware Factory has not been declared.", &middleware, &controller), controller_factory)
+        }
+    };
+    for name in middleware[..called].iter().rev() {
+        info!("Calling middleware {} after the controller...", &name);
+        response = match factory.after(request, name, response) {
+            Ok(response) => response,
+            Err(e) => error::render(request, 500, &e, controller_factory),
+        };
+    }
+    response
+}
+#[cfg(feature = "async")]
+/// Passes the `Request` to the async middleware stack and the controller linked to the route
+/// and returns the `Response` processed by the middleware in reverse order.
+pub(crate) async fn resolver_async(
+    request: &mut Request,
+    controller_factory: AsyncController,
+    middleware_factory: Option<&dyn AsyncMiddlewareFactory>,
+    controller: &str,
+    middleware: &[String],
+) -> Response {
+    let factory = match middleware_factory {
+        Some(factory) => factory,
+        None => {
+            let message = get_missing_factory_message(controller, middleware);
+            return error::render_async(request, 500, &message, controller_factory).await;
+        }
+    };
+    let mut called = 0;
+    let mut stopped = None;
+    for name in middleware {
+        info!("Calling middleware {}...", &name);
+        match get_stop(factory.before(request, name).await, controller) {
+            Ok(None) => called += 1,
+            Ok(Some(response)) => {
+                stopped = Some(response);
+                break;
+            }
+            Err(e) => {
+                stopped = Some(error::render_async(request, 500, &e, controller_factory).await);
+                break;
+            }
+        }
+    }
+    let mut response = match stopped {
+        Some(response) => response,
+        None => {
+            info!("Calling controller {}...", &controller);
+            match controller_factory(request, controller).await {
+                Ok(response) => response,
+                Err(e) => error::render_async(request, 500, &e, controller_factory).await,
+            }
+        }
+    };
+    for name in middleware[..called].iter().rev() {
+        info!("Calling middleware {} after the controller...", &name);
+        response = match factory.after(request, name, response).await {
+            Ok(response) => response,
+            Err(e) => error::render_async(request, 500, &e, controller_factory).await,
+        };
+    }
+    response
+}
+/// Returns the response which stops the middleware stack, if the `before` hook of a middleware failed,
+/// or `None` to call the next middleware.
+/// # Errors
+/// Returns the error message to be rendered if the middleware failed without response or returned an error.
+fn get_stop(result: Result<Outcome, String>, controller: &str) -> Result<Option<Response>, String> {
+    let outcome = result?;
+    if outcome.success {
+        info!("Middleware response is successful.");
+        return Ok(None);
+    }
+    info!("Middleware response is not successful.");
+    info!("Controller {} is skipped.", controller);
+    match outcome.response {
+        Some(response) => Ok(Some(response)),
+        None => Err("No Middleware response was set.".to_string()),
+    }
+}
+/// Returns the error message for a route with middleware when no middleware factory has been declared.
+fn get_missing_factory_message(controller: &str, middleware: &[String]) -> String {
+    format!(
+        "Middleware \"{}\" is set for Controller \"{}\" but Middleware Factory has not been declared.",
+        middleware.join(", "),
+        controller
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_stack() {
+        crate::tests::set_config();
+        assert_eq!(get_stack(" auth, csrf ,"), vec!["auth", "csrf"]);
+        assert!(get_stack("").is_empty());
+    }
+    #[test]
+    fn test_get_stop() {
+        let outcome = |success, response| Outcome { success, response };
+        assert!(get_stop(Ok(outcome(true, None)), "home").unwrap().is_none());
+        let response = get_stop(
+            Ok(outcome(false, Some(Response::new().set_status(401)))),
+            "home",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(response.get_status().unwrap().as_u16(), 401);
+        assert_eq!(
+            get_stop(Ok(outcome(false, None)), "home").unwrap_err(),
+            "No Middleware response was set."
+        );
+        assert_eq!(
+            get_stop(Err("Forbidden.".to_string()), "home").unwrap_err(),
+            "Forbidden."
+        );
     }
 }
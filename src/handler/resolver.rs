@@ -1,57 +1,173 @@
 //! Module for the resolver which handles the tcp stream.
 
 use crate::{
-    handler::{asset, controller, middleware::Outcome},
-    http::{request, request::Request, response::Response},
+    handler::{
+        asset,
+        controller::{self, ControllerFactory},
+        error,
+        middleware::MiddlewareFactory,
+    },
+    http::{extensions::Extensions, request::Request, response::Response},
+    server::connection::{Connection, FrameError, Message, Peer},
     settings,
     storage::cookie::Cookie,
 };
-use buf_redux::BufReader;
+#[cfg(feature = "async")]
+use crate::{
+    handler::{future::AsyncController, middleware::AsyncMiddlewareFactory},
+    server::connection::{self, AsyncConnection},
+};
 use chrono::offset::Utc;
 use log::{debug, error, info, warn};
-use std::{io::prelude::*, net::TcpStream};
+#[cfg(feature = "async")]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{
+    io::{Read, Write},
+    sync::Arc,
+};
 
-/// Creates the `Request` object, passes it to the handlers and writes the `response` in the tcp stream.
-pub fn execute(
+/// Reads the requests sent through the tcp stream, passes them to the handlers and writes the `response` of each one in the tcp stream.
+/// The connection is kept alive between requests unless the browser or the settings say otherwise,
+/// or `can_keep_alive` returns `false` (the server is shutting down or no other worker is free).
+pub fn execute<S: Read + Write>(
+    settings_file_path: &str,
+    stream: S,
+    peer: Peer,
+    controller: &ControllerFactory,
+    middleware: Option<&dyn MiddlewareFactory>,
+    state: &Arc<Extensions>,
+    can_keep_alive: &dyn Fn() -> bool,
+) {
+    let mut keep_alive = KeepAlive::from_settings();
+    let mut connection = Connection::new(
+        stream,
+        settings::max_header_size(),
+        settings::max_body_size(),
+    );
+    while let Some(message) = connection.read_message() {
+        let mut request = match read_request(message, &peer, state, keep_alive.timeout) {
+            Ok(request) => request,
+            Err(rejection) => {
+                if let Err(e) = connection.write(&rejection) {
+                    warn!("{}", e);
+                }
+                break;
+            }
+        };
+        let keep_alive = keep_alive.next(&request, can_keep_alive());
+        let keep_alive = match get_response(
+            &mut request,
+            settings_file_path,
+            controller,
+            middleware,
+            keep_alive,
+        ) {
+            Some(response) => send(&mut connection, &response) && response.is_keep_alive(),
+            None => false,
+        };
+        info!("End processing request for {}", &request.get_uri());
+        if !keep_alive || !can_keep_alive() {
+            break;
+        }
+    }
+}
+#[cfg(feature = "async")]
+/// Reads the requests sent through the tokio tcp stream, passes them to the async handlers and writes the `response` of each one in the tcp stream.
+/// The connection is kept alive between requests unless the browser, the settings or a server shutdown say otherwise.
+pub(crate) async fn execute_async(
     settings_file_path: &str,
-    mut stream: TcpStream,
-    controller: &fn(&Request, &str) -> Result<Response, String>,
-    middleware: Option<&fn(&mut Request, &str) -> Result<Outcome, String>>,
+    stream: tokio::net::TcpStream,
+    peer: Peer,
+    controller: AsyncController,
+    middleware: Option<&dyn AsyncMiddlewareFactory>,
+    state: &Arc<Extensions>,
+    shutdown: &AtomicBool,
 ) {
-    let mut data = Vec::new();
-    let mut buffer = [0; 4096];
-    let mut lector = BufReader::with_capacity(10240000, &mut stream);
-    loop {
-        let n = lector.read(&mut buffer).unwrap();
-        if n < 4096 {
-            data.extend_from_slice(&buffer[..n]);
+    let mut keep_alive = KeepAlive::from_settings();
+    let mut connection = AsyncConnection::new(
+        stream,
+        connection::get_idle_timeout(keep_alive.timeout),
+        settings::max_header_size(),
+        settings::max_body_size(),
+    );
+    while let Some(message) = connection.read_message().await {
+        let mut request = match read_request(message, &peer, state, keep_alive.timeout) {
+            Ok(request) => request,
+            Err(rejection) => {
+                if let Err(e) = connection.write(&rejection).await {
+                    warn!("{}", e);
+                }
+                break;
+            }
+        };
+        let keep_alive = keep_alive.next(&request, !shutdown.load(Ordering::SeqCst));
+        let keep_alive = match get_response_async(
+            &mut request,
+            settings_file_path,
+            controller,
+            middleware,
+            keep_alive,
+        )
+        .await
+        {
+            Some(response) => {
+                send_async(&mut connection, &response).await && response.is_keep_alive()
+            }
+            None => false,
+        };
+        info!("End processing request for {}", &request.get_uri());
+        if !keep_alive || shutdown.load(Ordering::SeqCst) {
             break;
-        } else {
-            data.extend_from_slice(&buffer[..n]);
         }
     }
-    match request::Request::new(&data) {
+}
+/// The keep-alive settings of a connection and the number of requests it has served.
+struct KeepAlive {
+    timeout: u64,
+    max_requests: u64,
+    served_requests: u64,
+}
+impl KeepAlive {
+    /// Returns the keep-alive settings of a new connection.
+    fn from_settings() -> KeepAlive {
+        KeepAlive {
+            timeout: settings::keep_alive_timeout(),
+            max_requests: settings::max_requests_per_connection(),
+            served_requests: 0,
+        }
+    }
+    /// Counts the given request and returns whether the connection can be kept alive after it,
+    /// provided that the server allows it.
+    fn next(&mut self, request: &Request, allowed: bool) -> bool {
+        self.served_requests += 1;
+        self.timeout > 0
+            && self.served_requests < self.max_requests
+            && request.is_keep_alive()
+            && allowed
+    }
+}
+/// Returns the `Request` of the message read from the tcp stream, with the peer and the state of the app.
+/// # Errors
+/// Returns the error `response` to be written in the tcp stream if the message cannot be read or parsed.
+fn read_request(
+    message: Result<Message, FrameError>,
+    peer: &Peer,
+    state: &Arc<Extensions>,
+    keep_alive_timeout: u64,
+) -> Result<Request, Vec<u8>> {
+    let message = message.map_err(|e| reject(e, keep_alive_timeout))?;
+    match Request::new_with_upload(&message.data, message.upload) {
         Some(mut request) => {
+            request.set_peer(peer.addr, peer.secure);
+            request.set_state(Arc::clone(state));
             info!("");
             info!("Start processing new request for {}", &request.get_uri());
-            stream
-                .write(&get_response(
-                    &mut request,
-                    settings_file_path,
-                    controller,
-                    middleware,
-                ))
-                .unwrap();
-            stream.flush().unwrap();
-            info!("End processing request for {}", &request.get_uri());
+            Ok(request)
         }
         None => {
-            if data.len() == 0 {
-                debug!("Empty request.");
-            } else {
-                warn!("The following request could not be processed:");
-                warn!("{}", String::from_utf8_lossy(&data[..]).to_string());
-            }
+            log_invalid_request(&message.data);
+            let e = FrameError::BadRequest("The request line cannot be parsed.".to_string());
+            Err(reject(e, keep_alive_timeout))
         }
     }
 }
@@ -59,9 +175,70 @@ pub fn execute(
 fn get_response(
     request: &mut Request,
     settings_file_path: &str,
-    controller: &fn(&Request, &str) -> Result<Response, String>,
-    middleware: Option<&fn(&mut Request, &str) -> Result<Outcome, String>>,
-) -> Vec<u8> {
+    controller: &ControllerFactory,
+    middleware: Option<&dyn MiddlewareFactory>,
+    keep_alive: bool,
+) -> Option<Response> {
+    prepare(request, settings_file_path);
+    if asset::is_static_file(request) {
+        asset::serve_static(request, keep_alive)
+    } else {
+        let response = controller::resolver(request, controller, middleware);
+        Some(finish(request, response, keep_alive))
+    }
+}
+#[cfg(feature = "async")]
+/// Returns the `response` of the async handlers.
+async fn get_response_async(
+    request: &mut Request,
+    settings_file_path: &str,
+    controller: AsyncController,
+    middleware: Option<&dyn AsyncMiddlewareFactory>,
+    keep_alive: bool,
+) -> Option<Response> {
+    prepare(request, settings_file_path);
+    if asset::is_static_file(request) {
+        asset::serve_static(request, keep_alive)
+    } else {
+        let response = controller::resolver_async(request, controller, middleware).await;
+        Some(finish(request, response, keep_alive))
+    }
+}
+/// Writes the `response` in the tcp stream, streaming its content if needed, and returns whether it has been sent.
+fn send<S: Read + Write>(connection: &mut Connection<S>, response: &Response) -> bool {
+    let result = connection
+        .write(&response.create())
+        .and_then(|()| match response.take_stream() {
+            Some(mut reader) => connection.write_stream(&mut reader, response.is_chunked()),
+            None => Ok(()),
+        });
+    match result {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("{}", e);
+            false
+        }
+    }
+}
+#[cfg(feature = "async")]
+/// Writes the `response` in the tokio tcp stream, streaming its content if needed, and returns whether it has been sent.
+async fn send_async(connection: &mut AsyncConnection, response: &Response) -> bool {
+    let mut result = connection.write(&response.create()).await;
+    if let (Ok(()), Some(mut reader)) = (&result, response.take_stream()) {
+        result = connection
+            .write_stream(&mut reader, response.is_chunked())
+            .await;
+    }
+    match result {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("{}", e);
+            false
+        }
+    }
+}
+/// Refreshes the configuration parameters if needed before the `Request` is handled.
+fn prepare(request: &Request, settings_file_path: &str) {
     if !settings::is_prod() && refresh_config() {
         crate::set_config(settings_file_path);
         crate::set_routes();
@@ -69,45 +246,288 @@ fn get_response(
         crate::set_messages();
     }
     debug!("{:#?}", &request);
-    if asset::is_static_file(&request) {
-        asset::serve_static(&request)
-    } else {
-        let mut response = controller::resolver(request, &controller, middleware);
-        match settings::get_string("cookie.renew") {
-            Ok(renew) => {
-                for cookie in kalgan_string::strip(&renew, ',').split(",") {
-                    match settings::get_string(&format!("cookie.{}.name", cookie)) {
-                        Ok(cookie_name) => {
-                            if request.get_cookies().contains_key(&cookie_name) {
-                                response.add_cookie(
-                                    Cookie::new()
-                                        .set_from_settings(cookie)
-                                        .set_value(request.get_cookies()[&cookie_name].clone())
-                                        .clone(),
-                                );
-                            }
-                        }
-                        Err(e) => {
-                            error!("{}", e);
-                            error!(
-                                "Cookie {} is set to be renewed but it's not defined.",
-                                cookie
-                            );
-                        }
+}
+/// Renews the cookies set in the settings and returns the `response` to be written in the tcp stream.
+/// Streamed responses close HTTP/1.0 connections since they cannot be sent in chunks.
+fn finish(request: &Request, mut response: Response, keep_alive: bool) -> Response {
+    if let Ok(renew) = settings::get_string("cookie.renew") {
+        for cookie in kalgan_string::strip(&renew, ',').split(',') {
+            match settings::get_string(&format!("cookie.{}.name", cookie)) {
+                Ok(cookie_name) => {
+                    if request.get_cookies().contains_key(&cookie_name) {
+                        response.add_cookie(
+                            Cookie::new()
+                                .set_from_settings(cookie)
+                                .set_value(request.get_cookies()[&cookie_name].clone())
+                                .clone(),
+                        );
                     }
                 }
+                Err(e) => {
+                    error!("{}", e);
+                    error!(
+                        "Cookie {} is set to be renewed but it's not defined.",
+                        cookie
+                    );
+                }
             }
-            Err(_e) => (),
         }
-        response.create()
+    }
+    let keep_alive = keep_alive && (!response.is_stream() || request.get_protocol() == "HTTP/1.1");
+    response
+        .set_head(request.get_method().eq_ignore_ascii_case("HEAD"))
+        .set_connection(keep_alive, settings::keep_alive_timeout())
+}
+/// Returns the error `response` for a message that couldn't be read from the tcp stream.
+fn reject(e: FrameError, keep_alive_timeout: u64) -> Vec<u8> {
+    let response = match e {
+        FrameError::PayloadTooLarge => {
+            error::render_without_request(413, "The request body exceeds the maximum size allowed.")
+        }
+        FrameError::HeaderTooLarge => error::render_without_request(
+            431,
+            "The request header fields exceed the maximum size allowed.",
+        ),
+        FrameError::BadRequest(message) => error::render_without_request(400, &message),
+        FrameError::Internal(message) => {
+            error!("{}", message);
+            error::render_without_request(500, "The request body cannot be stored.")
+        }
+    };
+    response.set_connection(false, keep_alive_timeout).create()
+}
+/// Logs a message that couldn't be parsed as a `Request`.
+fn log_invalid_request(data: &[u8]) {
+    if data.is_empty() {
+        debug!("Empty request.");
+    } else {
+        warn!("The following request could not be processed:");
+        warn!("{}", String::from_utf8_lossy(data));
     }
 }
 /// Updates `Refresh` struct with the current time.
 fn refresh_config() -> bool {
     let mut refresh = crate::REFRESH.lock().unwrap();
-    let old = refresh.time.clone();
+    let old = refresh.time;
     let elapsed = Utc::now().time();
     refresh.time = elapsed;
     std::mem::drop(refresh);
     (elapsed - old).num_seconds() as u64 > settings::refresh_config_timeout()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        collections::HashMap,
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    fn controller(request: &Request, controller: &str) -> Result<Response, String> {
+        match controller {
+            "hello_world" => Ok(Response::new()
+                .set_status(200)
+                .set_content_type("text/plain")
+                .set_content("Hello World")),
+            "export" => Ok(Response::new()
+                .set_status(200)
+                .set_content_type("text/csv")
+                .set_stream_iter(vec![b"id,name\n".to_vec(), b"1,John\n".to_vec()])),
+            "user_create" => {
+                let user: HashMap<String, u32> = request.json()?;
+                Ok(Response::new()
+                    .set_status(201)
+                    .set_content(&user["age"].to_string()))
+            }
+            "client" => Ok(Response::new().set_status(200).set_content(&format!(
+                "{} {}://{}",
+                request.client_ip().unwrap(),
+                request.client_scheme(),
+                request.client_host()
+            ))),
+            "post_show" => {
+                let id: i64 = request.param_as("id")?;
+                Ok(Response::new().set_status(200).set_content(&id.to_string()))
+            }
+            "upload" => {
+                let file = &request.get_files()["file"];
+                let mut content = String::new();
+                file.reader()
+                    .and_then(|mut reader| reader.read_to_string(&mut content))
+                    .map_err(|e| e.to_string())?;
+                Ok(Response::new().set_status(200).set_content(&format!(
+                    "{} {} {} {}",
+                    request.get_input()["title"],
+                    file.filename,
+                    file.path().is_some(),
+                    content
+                )))
+            }
+            _ => Err(format!("Controller {} not found in resolver.", &controller)),
+        }
+    }
+    fn serve(requests: &[u8]) -> String {
+        crate::tests::set_config();
+        crate::mock_routes();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, addr) = listener.accept().unwrap();
+            let controller: fn(&Request, &str) -> Result<Response, String> = controller;
+            execute(
+                "tests/mock/settings.yaml",
+                stream,
+                Peer {
+                    addr: Some(addr),
+                    secure: false,
+                },
+                &controller,
+                None,
+                &Arc::default(),
+                &|| true,
+            );
+        });
+        let mut client = TcpStream::connect(address).unwrap();
+        client.write_all(requests).unwrap();
+        let mut responses = String::new();
+        client.read_to_string(&mut responses).unwrap();
+        server.join().unwrap();
+        responses
+    }
+
+    #[test]
+    fn test_execute_keeps_connection_alive() {
+        let responses = serve(
+            b"GET /hello-world HTTP/1.1\r\nHost: localhost\r\n\r\nGET /hello-world HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        );
+        assert_eq!(responses.matches("HTTP/1.1 200 OK").count(), 2);
+        assert!(responses.contains("Connection: keep-alive"));
+        assert!(responses.ends_with("Connection: close\r\n\r\nHello World"));
+    }
+    #[test]
+    fn test_execute_reads_chunked_body() {
+        let responses = serve(
+            b"POST /hello-world HTTP/1.1\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n3\r\nfoo\r\n4\r\n=bar\r\n0\r\n\r\n",
+        );
+        assert!(responses.starts_with("HTTP/1.1 200 OK"));
+    }
+    #[test]
+    fn test_execute_rejects_too_large_body() {
+        let responses = serve(b"POST /hello-world HTTP/1.1\r\nContent-Length: 2097152\r\n\r\n");
+        assert!(responses.starts_with("HTTP/1.1 413 Payload Too Large"));
+        assert!(responses.contains("Connection: close"));
+    }
+    #[test]
+    fn test_execute_rejects_too_large_header() {
+        let mut request = b"GET /hello-world HTTP/1.1\r\nCookie: ".to_vec();
+        request.resize(16384, b'a');
+        let responses = serve(&request);
+        assert!(responses.starts_with("HTTP/1.1 431 Request Header Fields Too Large"));
+    }
+    #[test]
+    fn test_execute_rejects_invalid_request() {
+        let responses = serve(b"GARBAGE\r\n\r\nGET /hello-world HTTP/1.1\r\n\r\n");
+        assert!(responses.starts_with("HTTP/1.1 400 Bad Request"));
+        assert!(responses.contains("Connection: close"));
+        assert!(!responses.contains("Hello World"));
+    }
+    #[test]
+    fn test_execute_closes_http_1_0_connection() {
+        let responses = serve(
+            b"GET /hello-world HTTP/1.0\r\nHost: localhost\r\n\r\nGET /hello-world HTTP/1.0\r\n\r\n",
+        );
+        assert_eq!(responses.matches("HTTP/1.1 200 OK").count(), 1);
+        assert!(responses.contains("Connection: close"));
+    }
+    #[test]
+    fn test_execute_answers_head_request() {
+        let responses = serve(
+            b"HEAD /hello-world HTTP/1.1\r\nHost: localhost\r\n\r\nGET /hello-world HTTP/1.1\r\nConnection: close\r\n\r\n",
+        );
+        assert!(responses.starts_with("HTTP/1.1 200 OK"));
+        assert!(responses.contains("Content-Length: 11\r\nConnection: keep-alive\r\nKeep-Alive: timeout=5\r\n\r\nHTTP/1.1 200 OK"));
+        assert_eq!(responses.matches("Hello World").count(), 1);
+    }
+    #[test]
+    fn test_execute_rejects_invalid_json() {
+        let responses = serve(
+            b"POST /users HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: 5\r\n\r\n{\"a\":POST /users HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: 15\r\n\r\n{\"age\": \"many\"}POST /users HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: 11\r\nConnection: close\r\n\r\n{\"age\": 42}",
+        );
+        assert!(responses.starts_with("HTTP/1.1 400 Bad Request"));
+        assert!(responses.contains("HTTP/1.1 422 Unprocessable Content"));
+        assert!(responses.contains("HTTP/1.1 201 Created"));
+    }
+    #[test]
+    fn test_execute_streams_response_in_chunks() {
+        let responses = serve(
+            b"GET /export HTTP/1.1\r\nHost: localhost\r\n\r\nGET /hello-world HTTP/1.1\r\nConnection: close\r\n\r\n",
+        );
+        assert!(responses.contains(
+            "Transfer-Encoding: chunked\r\nConnection: keep-alive\r\nKeep-Alive: timeout=5\r\n\r\n8\r\nid,name\n\r\n7\r\n1,John\n\r\n0\r\n\r\nHTTP/1.1 200 OK"
+        ));
+    }
+    #[test]
+    fn test_execute_streams_response_to_http_1_0() {
+        let responses = serve(b"GET /export HTTP/1.0\r\nConnection: keep-alive\r\n\r\n");
+        assert!(!responses.contains("Transfer-Encoding"));
+        assert!(responses.ends_with("Connection: close\r\n\r\nid,name\n1,John\n"));
+    }
+    #[test]
+    fn test_execute_reads_upload() {
+        let body = "--b\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nHoliday\r\n--b\r\nContent-Disposition: form-data; name=\"file\"; filename=\"beach.txt\"\r\n\r\nsun, sand and sea\r\n--b--\r\n";
+        let responses = serve(format!("POST /upload HTTP/1.1\r\nContent-Type: multipart/form-data; boundary=b\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body).as_bytes());
+        assert!(responses.starts_with("HTTP/1.1 200 OK"));
+        assert!(responses.ends_with("Holiday beach.txt true sun, sand and sea"));
+    }
+    #[test]
+    fn test_execute_reads_chunked_upload() {
+        let body = "--b\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nHoliday\r\n--b\r\nContent-Disposition: form-data; name=\"file\"; filename=\"beach.txt\"\r\n\r\nsun, sand and sea\r\n--b--\r\n";
+        let (first, second) = body.split_at(60);
+        let responses = serve(format!("POST /upload HTTP/1.1\r\nContent-Type: multipart/form-data; boundary=b\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n{:X}\r\n{}\r\n{:X}\r\n{}\r\n0\r\n\r\n", first.len(), first, second.len(), second).as_bytes());
+        assert!(responses.starts_with("HTTP/1.1 200 OK"));
+        assert!(responses.ends_with("Holiday beach.txt true sun, sand and sea"));
+        let responses = serve(b"POST /upload HTTP/1.1\r\nContent-Type: multipart/form-data; boundary=b\r\nTransfer-Encoding: chunked\r\n\r\n2000\r\n");
+        assert!(responses.starts_with("HTTP/1.1 413 Payload Too Large"));
+    }
+    #[test]
+    fn test_execute_rejects_too_large_upload() {
+        let responses = serve(b"POST /upload HTTP/1.1\r\nContent-Type: multipart/form-data; boundary=b\r\nContent-Length: 8192\r\n\r\n");
+        assert!(responses.starts_with("HTTP/1.1 413 Payload Too Large"));
+        let field = "x".repeat(128);
+        let body = format!("--b\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\r\n{}\r\n--b--\r\n", field);
+        let responses = serve(format!("POST /upload HTTP/1.1\r\nContent-Type: multipart/form-data; boundary=b\r\nContent-Length: {}\r\n\r\n{}", body.len(), body).as_bytes());
+        assert!(responses.starts_with("HTTP/1.1 413 Payload Too Large"));
+    }
+    #[test]
+    fn test_execute_rejects_invalid_route_parameter() {
+        let responses = serve(
+            b"GET /posts/abc HTTP/1.1\r\n\r\nGET /posts/42 HTTP/1.1\r\nConnection: close\r\n\r\n",
+        );
+        assert!(responses.starts_with("HTTP/1.1 404 Not Found"));
+        assert!(responses.contains("HTTP/1.1 200 OK"));
+        assert!(responses.ends_with("\r\n\r\n42"));
+    }
+    #[test]
+    fn test_execute_ignores_forwarded_headers_of_untrusted_peer() {
+        let responses = serve(
+            b"GET /client HTTP/1.1\r\nHost: localhost\r\nX-Forwarded-For: 192.0.2.43\r\nX-Forwarded-Proto: https\r\nX-Forwarded-Host: example.com\r\nConnection: close\r\n\r\n",
+        );
+        assert!(responses.ends_with("\r\n\r\n127.0.0.1 http://localhost"));
+    }
+    #[test]
+    fn test_execute_rejects_method_not_allowed() {
+        let responses =
+            serve(b"GET /users HTTP/1.1\r\n\r\nGET /missing HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert!(responses.starts_with("HTTP/1.1 405 Method Not Allowed"));
+        assert!(responses.contains("\r\nAllow: OPTIONS, POST\r\n"));
+        assert!(responses.contains("HTTP/1.1 404 Not Found"));
+    }
+    #[test]
+    fn test_execute_answers_options_request() {
+        let responses =
+            serve(b"OPTIONS /users HTTP/1.1\r\n\r\nOPTIONS /missing HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert!(responses.starts_with("HTTP/1.1 204 No Content\r\nAllow: OPTIONS, POST\r\n"));
+        assert!(responses.contains("HTTP/1.1 404 Not Found"));
+    }
+}
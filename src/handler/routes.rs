@@ -0,0 +1,584 @@
+//! Module for the routes files, whose groups and includes are expanded into a list of routes
+//! matched against the path, method and host of the request.
+//!
+//! Routes can be grouped to share a path prefix, a middleware stack, the hosts they are restricted to
+//! and a default language, and other routes files can be included (relative to the including file):
+//! ```yaml
+//! routes:
+//!   - home:
+//!       path: /
+//!       controller: home_controller/index
+//!   - admin:
+//!       prefix: /admin
+//!       middleware: auth
+//!       host: admin.example.com
+//!       language: en
+//!       routes:
+//!         - admin_dashboard:
+//!             path: /dashboard
+//!             controller: admin/dashboard_controller/index
+//!             middleware: csrf
+//!         - include: admin/users.yaml
+//! ```
+//! Route names are unique, since urls are generated from them: a route whose name is already taken is skipped.
+
+use kalgan_router::{Route, Router};
+use log::{error, warn};
+use regex::Regex;
+use serde_yaml::{Mapping, Value};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The methods allowed by the routes without `methods`.
+const METHODS: [&str; 7] = ["DELETE", "GET", "HEAD", "OPTIONS", "PATCH", "POST", "PUT"];
+
+/// The compiled path and the hosts of a route, stored at the same index as the route in the router.
+#[derive(Debug)]
+pub(crate) struct Pattern {
+    segments: Vec<Segment>,
+    hosts: Vec<String>,
+}
+impl Pattern {
+    /// Returns the parameters of the path if it matches the pattern.
+    fn get_parameters(&self, path: &str) -> Option<HashMap<String, String>> {
+        let mut parameters = HashMap::new();
+        let mut start = 0;
+        for (index, segment) in self.segments.iter().enumerate() {
+            let rest = &path[start..];
+            match segment {
+                Segment::Literal(value) => {
+                    if !rest.starts_with(value.as_str()) {
+                        return None;
+                    }
+                    start += value.len();
+                }
+                Segment::Parameter(name, requirement) => {
+                    let rest = match self.segments.get(index + 1) {
+                        Some(Segment::Literal(next)) => &rest[..rest.find(next.as_str())?],
+                        _ => rest,
+                    };
+                    let value = requirement.find(rest).filter(|value| value.start() == 0)?;
+                    parameters.insert(name.to_string(), value.as_str().to_string());
+                    start += value.end();
+                }
+            }
+        }
+        if start == path.len() {
+            Some(parameters)
+        } else {
+            None
+        }
+    }
+    /// Checks whether the route is not restricted to hosts other than the given one.
+    fn allows_host(&self, host: &str) -> bool {
+        self.hosts.is_empty() || matches_host(&self.hosts, host)
+    }
+}
+/// A part of a route path: literal text or a parameter with its requirement.
+#[derive(Debug)]
+enum Segment {
+    Literal(String),
+    Parameter(String, Regex),
+}
+
+/// The settings a group passes down to its routes.
+#[derive(Debug, Clone, Default)]
+struct Group {
+    prefix: String,
+    middleware: Vec<String>,
+    hosts: Vec<String>,
+    language: Option<String>,
+    requirements: Mapping,
+}
+impl Group {
+    /// Returns the settings of the child group with the given keys.
+    fn merge(&self, keys: &Mapping) -> Group {
+        let mut requirements = self.requirements.clone();
+        if let Some(child_requirements) = get(keys, "requirements").and_then(Value::as_mapping) {
+            requirements.extend(child_requirements.clone());
+        }
+        Group {
+            prefix: match get_str(keys, "prefix") {
+                Some(prefix) => join_path(&self.prefix, prefix),
+                None => self.prefix.clone(),
+            },
+            middleware: self
+                .middleware
+                .iter()
+                .cloned()
+                .chain(split_list(get_str(keys, "middleware").unwrap_or_default()))
+                .collect(),
+            hosts: get_hosts(keys).unwrap_or_else(|| self.hosts.clone()),
+            language: get_str(keys, "language")
+                .map(|language| language.to_string())
+                .or_else(|| self.language.clone()),
+            requirements,
+        }
+    }
+}
+/// Parses the routes files of the given source (a file or a folder) and returns the router
+/// and the patterns of its routes.
+pub(crate) fn load(source: &str) -> (Router, Vec<Pattern>) {
+    let mut routes = Vec::new();
+    let path = Path::new(source);
+    if !path.exists() {
+        error!("Source path {} not found.", source);
+    }
+    for file in get_files(path) {
+        read_file(&file, &Group::default(), &mut Vec::new(), &mut routes);
+    }
+    let (collection, patterns) = routes.into_iter().unzip();
+    (Router { collection }, patterns)
+}
+/// Returns the first route matching the path and method which is not restricted to other hosts.
+/// # Errors
+/// Returns the error message if no route matches.
+pub(crate) fn find(
+    router: &Router,
+    patterns: &[Pattern],
+    path: &str,
+    method: &str,
+    host: &str,
+) -> Result<Route, String> {
+    let lowercase_method = method.to_lowercase();
+    for (route, pattern) in router.collection.iter().zip(patterns) {
+        if !(route.get_methods().is_empty() || route.get_methods().contains(&lowercase_method))
+            || !pattern.allows_host(host)
+        {
+            continue;
+        }
+        if let Some(parameters) = pattern.get_parameters(path) {
+            let mut route = route.clone();
+            if let Some(language) = parameters.get(&route.language) {
+                route.language = language.to_string();
+            }
+            route.parameters = parameters;
+            return Ok(route);
+        }
+    }
+    Err(format!(
+        "No route found for uri '{}' and method '{}'",
+        path, method
+    ))
+}
+/// Returns the methods (in uppercase and alphabetical order) of the routes matching the path
+/// which are not restricted to other hosts.
+/// An empty vector means that no route matches the path.
+pub(crate) fn get_allowed_methods(
+    router: &Router,
+    patterns: &[Pattern],
+    path: &str,
+    host: &str,
+) -> Vec<String> {
+    let mut methods = Vec::new();
+    for (route, pattern) in router.collection.iter().zip(patterns) {
+        if !pattern.allows_host(host) || pattern.get_parameters(path).is_none() {
+            continue;
+        }
+        if route.get_methods().is_empty() {
+            methods.extend(METHODS.iter().map(|method| method.to_string()));
+        } else {
+            methods.extend(
+                route
+                    .get_methods()
+                    .iter()
+                    .map(|method| method.to_uppercase()),
+            );
+        }
+    }
+    methods.sort();
+    methods.dedup();
+    methods
+}
+/// Checks whether the host (with or without port) is one of the given hosts,
+/// where `*.example.com` matches any subdomain of `example.com`.
+fn matches_host(hosts: &[String], host: &str) -> bool {
+    let host = if host.starts_with('[') {
+        host.split(']')
+            .next()
+            .map(|ip| &host[..ip.len() + 1])
+            .unwrap_or(host)
+    } else {
+        host.split(':').next().unwrap_or(host)
+    };
+    hosts
+        .iter()
+        .any(|pattern| match pattern.strip_prefix("*.") {
+            Some(domain) => {
+                host.len() > domain.len() + 1
+                    && host
+                        .to_lowercase()
+                        .ends_with(&format!(".{}", domain.to_lowercase()))
+            }
+            None => pattern.eq_ignore_ascii_case(host),
+        })
+}
+/// Returns the yaml files of the given folder (and its subfolders) in alphabetical order, or the given file.
+fn get_files(path: &Path) -> Vec<PathBuf> {
+    if !path.is_dir() {
+        return vec![path.to_path_buf()];
+    }
+    let mut entries: Vec<PathBuf> = match fs::read_dir(path) {
+        Ok(read_dir) => read_dir
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .collect(),
+        Err(e) => {
+            warn!("{}", e);
+            Vec::new()
+        }
+    };
+    entries.sort();
+    entries
+        .into_iter()
+        .flat_map(|entry| {
+            if entry.is_dir() {
+                get_files(&entry)
+            } else if matches!(
+                entry.extension().and_then(|extension| extension.to_str()),
+                Some(extension) if extension.eq_ignore_ascii_case("yaml")
+            ) {
+                vec![entry]
+            } else {
+                Vec::new()
+            }
+        })
+        .collect()
+}
+/// Adds the routes of the given file to the collection, within the given group.
+/// The files being read are kept in the stack to skip recursive includes.
+fn read_file(
+    path: &Path,
+    group: &Group,
+    stack: &mut Vec<PathBuf>,
+    routes: &mut Vec<(Route, Pattern)>,
+) {
+    let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if stack.contains(&canonical) {
+        warn!("Routes file {} is included recursively.", path.display());
+        return;
+    }
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            warn!("Routes file {} cannot be read: {}", path.display(), e);
+            return;
+        }
+    };
+    let document: Value = match serde_yaml::from_str(&content) {
+        Ok(document) => document,
+        Err(e) => {
+            warn!("Routes file {} cannot be parsed: {}", path.display(), e);
+            return;
+        }
+    };
+    match document.get("routes").and_then(Value::as_sequence) {
+        Some(entries) => {
+            stack.push(canonical);
+            let folder = path.parent().unwrap_or_else(|| Path::new(""));
+            expand(entries, group, folder, stack, routes);
+            stack.pop();
+        }
+        None => warn!("Routes file {} has no routes.", path.display()),
+    }
+}
+/// Adds the given routes, groups and includes to the collection, within the given group.
+fn expand(
+    entries: &[Value],
+    group: &Group,
+    folder: &Path,
+    stack: &mut Vec<PathBuf>,
+    routes: &mut Vec<(Route, Pattern)>,
+) {
+    for entry in entries {
+        if let Some(include) = entry.get("include").and_then(Value::as_str) {
+            read_file(&folder.join(include), group, stack, routes);
+            continue;
+        }
+        let (name, keys) = match entry
+            .as_mapping()
+            .and_then(|entry| entry.iter().next())
+            .and_then(|(name, keys)| Some((name.as_str()?, keys.as_mapping()?)))
+        {
+            Some(route) => route,
+            None => {
+                warn!("Invalid route: {:?}", entry);
+                continue;
+            }
+        };
+        match get(keys, "routes").and_then(Value::as_sequence) {
+            Some(children) => expand(children, &group.merge(keys), folder, stack, routes),
+            None if routes.iter().any(|(route, _)| route.get_name() == name) => {
+                warn!(
+                    "Route {} is already defined, the duplicate is skipped.",
+                    name
+                )
+            }
+            None => match apply(name, keys, group) {
+                Ok(route) => routes.push(route),
+                Err(e) => warn!("Invalid route {}: {}", name, e),
+            },
+        }
+    }
+}
+/// Returns the route with the settings of the group applied and its pattern.
+/// # Errors
+/// Returns the error message if the route has no controller or an invalid requirement.
+fn apply(name: &str, keys: &Mapping, group: &Group) -> Result<(Route, Pattern), String> {
+    let path = join_path(&group.prefix, get_str(keys, "path").unwrap_or_default());
+    let controller = get_str(keys, "controller").ok_or("Missing controller.")?;
+    let middleware: Vec<String> = group
+        .middleware
+        .iter()
+        .cloned()
+        .chain(split_list(get_str(keys, "middleware").unwrap_or_default()))
+        .collect();
+    let methods = split_list(get_str(keys, "methods").unwrap_or_default())
+        .map(|method| Value::String(method.to_lowercase()))
+        .collect();
+    let language = get_str(keys, "language")
+        .map(|language| language.to_string())
+        .or_else(|| group.language.clone())
+        .unwrap_or_default();
+    let mut requirements = group.requirements.clone();
+    if let Some(route_requirements) = get(keys, "requirements").and_then(Value::as_mapping) {
+        requirements.extend(route_requirements.clone());
+    }
+    let mut route = Mapping::new();
+    route.insert(key("name"), Value::String(name.to_string()));
+    route.insert(key("path"), Value::String(path.to_string()));
+    route.insert(key("methods"), Value::Sequence(methods));
+    route.insert(
+        key("controller"),
+        Value::String(controller.replace('/', "::")),
+    );
+    route.insert(key("middleware"), Value::String(middleware.join(", ")));
+    route.insert(key("parameters"), Value::Mapping(Mapping::new()));
+    route.insert(key("language"), Value::String(language));
+    let route = serde_yaml::from_value(Value::Mapping(route)).map_err(|e| e.to_string())?;
+    let pattern = Pattern {
+        segments: get_segments(&path, &requirements)?,
+        hosts: get_hosts(keys).unwrap_or_else(|| group.hosts.clone()),
+    };
+    Ok((route, pattern))
+}
+/// Returns the segments of the path, where `{name}` is a parameter matching its requirement
+/// (`[^/]+` by default).
+/// # Errors
+/// Returns the error message if a requirement is not a valid regular expression.
+fn get_segments(path: &str, requirements: &Mapping) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    for parameter in Regex::new(r"\{.+?\}").unwrap().find_iter(path) {
+        if start < parameter.start() {
+            segments.push(Segment::Literal(path[start..parameter.start()].to_string()));
+        }
+        let name = &path[parameter.start() + 1..parameter.end() - 1];
+        let requirement = Regex::new(get_str(requirements, name).unwrap_or("[^/]+"))
+            .map_err(|e| e.to_string())?;
+        segments.push(Segment::Parameter(name.to_string(), requirement));
+        start = parameter.end();
+    }
+    if start < path.len() {
+        segments.push(Segment::Literal(path[start..].to_string()));
+    }
+    Ok(segments)
+}
+/// Returns the hosts of the `host` key, written as a string or a list.
+fn get_hosts(keys: &Mapping) -> Option<Vec<String>> {
+    match get(keys, "host")? {
+        Value::String(host) => Some(vec![host.to_string()]),
+        Value::Sequence(hosts) => Some(
+            hosts
+                .iter()
+                .filter_map(|host| host.as_str().map(|host| host.to_string()))
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+/// Joins the path to the prefix with a single slash between them.
+fn join_path(prefix: &str, path: &str) -> String {
+    let prefix = prefix.trim_end_matches('/');
+    match path.trim_start_matches('/') {
+        "" if !prefix.is_empty() => prefix.to_string(),
+        path => format!("{}/{}", prefix, path),
+    }
+}
+/// Splits the comma separated values.
+fn split_list(list: &str) -> impl Iterator<Item = String> + '_ {
+    list.split(',')
+        .map(|value| value.trim())
+        .filter(|value| !value.is_empty())
+        .map(|value| value.to_string())
+}
+fn get<'m>(keys: &'m Mapping, name: &str) -> Option<&'m Value> {
+    keys.get(&key(name))
+}
+fn get_str<'m>(keys: &'m Mapping, name: &str) -> Option<&'m str> {
+    get(keys, name).and_then(Value::as_str)
+}
+fn key(name: &str) -> Value {
+    Value::String(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROUTES: &str = "routes:
+  - home:
+      path: /
+      controller: home
+  - admin:
+      prefix: /admin/
+      middleware: auth
+      host: admin.example.com
+      language: en
+      routes:
+        - admin_home:
+            path: /
+            controller: admin/home
+        - include: users.yaml
+";
+    const USERS: &str = "routes:
+  - users:
+      prefix: users
+      middleware: csrf, audit
+      routes:
+        - user_show:
+            path: /{id}
+            controller: admin/users/show
+            methods: get, put
+            middleware: cache
+            language: es
+            host: '*.example.org'
+        - include: routes.yaml
+";
+
+    #[test]
+    fn test_load_and_find() {
+        let folder = tempfile::tempdir().unwrap();
+        fs::write(folder.path().join("routes.yaml"), ROUTES).unwrap();
+        fs::write(folder.path().join("users.yaml"), USERS).unwrap();
+        let (router, patterns) = load(&folder.path().join("routes.yaml").to_string_lossy());
+        assert_eq!(router.collection.len(), 3);
+        assert_eq!(patterns[1].hosts, vec!["admin.example.com"]);
+        let route = find(
+            &router,
+            &patterns,
+            "/admin",
+            "GET",
+            "admin.example.com:8080",
+        )
+        .unwrap();
+        assert_eq!(route.get_name(), "admin_home");
+        assert_eq!(route.get_middleware(), "auth");
+        assert_eq!(route.language, "en");
+        assert!(find(&router, &patterns, "/admin", "GET", "example.com").is_err());
+        let route = find(
+            &router,
+            &patterns,
+            "/admin/users/7",
+            "GET",
+            "api.example.org",
+        )
+        .unwrap();
+        assert_eq!(route.get_controller(), "admin::users::show");
+        assert_eq!(route.get_middleware(), "auth, csrf, audit, cache");
+        assert_eq!(route.language, "es");
+        assert_eq!(route.parameters["id"], "7");
+        assert!(find(&router, &patterns, "/admin/users/7", "GET", "example.org").is_err());
+        assert!(find(&router, &patterns, "/", "GET", "anything").is_ok());
+    }
+    #[test]
+    fn test_get_allowed_methods() {
+        let folder = tempfile::tempdir().unwrap();
+        fs::write(folder.path().join("routes.yaml"), ROUTES).unwrap();
+        fs::write(folder.path().join("users.yaml"), USERS).unwrap();
+        let (router, patterns) = load(&folder.path().join("routes.yaml").to_string_lossy());
+        assert!(find(
+            &router,
+            &patterns,
+            "/admin/users/7",
+            "POST",
+            "api.example.org"
+        )
+        .is_err());
+        assert_eq!(
+            get_allowed_methods(&router, &patterns, "/admin/users/7", "api.example.org"),
+            vec!["GET", "PUT"]
+        );
+        assert!(
+            get_allowed_methods(&router, &patterns, "/admin/users/7", "example.org").is_empty()
+        );
+        assert!(get_allowed_methods(&router, &patterns, "/missing", "example.org").is_empty());
+        assert_eq!(
+            get_allowed_methods(&router, &patterns, "/", "example.org").len(),
+            7
+        );
+    }
+    #[test]
+    fn test_find_on_other_host() {
+        let folder = tempfile::tempdir().unwrap();
+        fs::write(
+            folder.path().join("routes.yaml"),
+            "routes:
+  - page:
+      path: /{language}/page/{id}
+      controller: example/page
+      host: example.com
+      language: language
+      requirements:
+        id: '[0-9]+'
+  - page_org:
+      path: /{language}/page/{id}
+      controller: example_org/page
+      host: example.org
+",
+        )
+        .unwrap();
+        let (router, patterns) = load(&folder.path().join("routes.yaml").to_string_lossy());
+        let route = find(&router, &patterns, "/es/page/7", "GET", "example.com").unwrap();
+        assert_eq!(route.get_controller(), "example::page");
+        assert_eq!(route.language, "es");
+        assert_eq!(route.parameters["id"], "7");
+        assert!(find(&router, &patterns, "/es/page/a", "GET", "example.com").is_err());
+        let route = find(&router, &patterns, "/es/page/a", "GET", "example.org").unwrap();
+        assert_eq!(route.get_controller(), "example_org::page");
+        assert_eq!(route.parameters["id"], "a");
+    }
+    #[test]
+    fn test_load_skips_duplicate_names() {
+        let folder = tempfile::tempdir().unwrap();
+        fs::write(
+            folder.path().join("routes.yaml"),
+            "routes:
+  - page:
+      path: /page
+      controller: page
+  - admin:
+      prefix: /admin
+      routes:
+        - page:
+            path: /page
+            controller: admin/page
+",
+        )
+        .unwrap();
+        let (router, patterns) = load(&folder.path().join("routes.yaml").to_string_lossy());
+        assert_eq!(router.collection.len(), 1);
+        assert_eq!(patterns.len(), 1);
+        assert!(find(&router, &patterns, "/admin/page", "GET", "example.com").is_err());
+    }
+    #[test]
+    fn test_join_path() {
+        assert_eq!(join_path("", "/"), "/");
+        assert_eq!(join_path("", "hello"), "/hello");
+        assert_eq!(join_path("/admin/", "/"), "/admin");
+        assert_eq!(join_path("/admin", "users/{id}"), "/admin/users/{id}");
+    }
+}
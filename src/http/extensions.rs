@@ -0,0 +1,124 @@
+//! Module for the typed values attached to the request object by the middleware to be read by the controller.
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt,
+    sync::Arc,
+};
+
+#[derive(Clone, Default)]
+/// A map of values indexed by their type, where at most one value of each type is kept.
+/// Values are shared by the clones of the request, so they must be `Send + Sync`.
+/// # Examples
+/// ```
+/// use kalgan::handler::middleware::Outcome;
+/// use kalgan::http::{request::Request, response::Response};
+///
+/// struct User {
+///     id: u32,
+/// }
+/// fn middleware(request: &mut Request, _middleware: &str) -> Result<Outcome, String> {
+///     request.extensions_mut().insert(User { id: 42 });
+///     Ok(Outcome { success: true, response: None })
+/// }
+/// fn controller(request: &Request, _controller: &str) -> Result<Response, String> {
+///     let user = request.extensions().get::<User>().ok_or("User not found.")?;
+///     Ok(Response::new().set_status(200).set_content(&user.id.to_string()))
+/// }
+/// ```
+pub struct Extensions {
+    map: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+impl Extensions {
+    /// Creates and returns an empty instance of `Extensions`.
+    pub fn new() -> Extensions {
+        Extensions::default()
+    }
+    /// Inserts the value, replacing the previous value of the same type (if any).
+    /// # Examples
+    /// ```
+    /// use kalgan::http::extensions::Extensions;
+    ///
+    /// let mut extensions = Extensions::new();
+    /// extensions.insert("tenant".to_string());
+    /// extensions.insert(5u8);
+    /// # assert_eq!(extensions.len(), 2)
+    /// ```
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) {
+        self.map.insert(TypeId::of::<T>(), Arc::new(value));
+    }
+    /// Returns a reference to the value of the given type (if any).
+    /// # Examples
+    /// ```
+    /// use kalgan::http::extensions::Extensions;
+    ///
+    /// let mut extensions = Extensions::new();
+    /// extensions.insert(5u8);
+    /// let value: Option<&u8> = extensions.get::<u8>();
+    /// # assert_eq!(value, Some(&5));
+    /// # assert_eq!(extensions.get::<u16>(), None)
+    /// ```
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.map
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+    }
+    /// Checks whether there is a value of the given type.
+    pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+        self.map.contains_key(&TypeId::of::<T>())
+    }
+    /// Removes the value of the given type and returns whether it was present.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::extensions::Extensions;
+    ///
+    /// let mut extensions = Extensions::new();
+    /// extensions.insert(5u8);
+    /// # assert!(extensions.remove::<u8>());
+    /// # assert!(!extensions.contains::<u8>())
+    /// ```
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> bool {
+        self.map.remove(&TypeId::of::<T>()).is_some()
+    }
+    /// Returns the number of values.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+    /// Checks whether there are no values.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+impl fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Extensions")
+            .field("len", &self.map.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Tenant(String);
+
+    #[test]
+    fn test_insert_replaces_value_of_same_type() {
+        let mut extensions = Extensions::new();
+        extensions.insert(Tenant("acme".to_string()));
+        extensions.insert(Tenant("globex".to_string()));
+        extensions.insert(1u32);
+        assert_eq!(extensions.len(), 2);
+        assert_eq!(
+            extensions.get::<Tenant>(),
+            Some(&Tenant("globex".to_string()))
+        );
+        let clone = extensions.clone();
+        assert!(extensions.remove::<Tenant>());
+        assert_eq!(extensions.get::<Tenant>(), None);
+        assert!(clone.contains::<Tenant>());
+    }
+}
@@ -0,0 +1,85 @@
+//! Module for the errors raised when the data of the request object cannot be extracted into a serde type.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+/// The reasons why the data of the request cannot be extracted.
+/// The handler answers with its status code when the controller or middleware fails with it (such as when it's propagated with `?`).
+/// # Examples
+/// ```
+/// use kalgan::http::{request::Request, response::Response};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct User {
+///     name: String,
+/// }
+/// fn create(request: &Request) -> Result<Response, String> {
+///     let user: User = request.json()?;
+///     Ok(Response::new().set_status(201).set_content(&user.name))
+/// }
+/// ```
+pub enum ExtractError {
+    /// The `Content-Type` of the request is not the expected one (400 Bad Request).
+    ContentType(String),
+    /// The data is malformed (400 Bad Request).
+    Syntax(String),
+    /// The data is well-formed but it doesn't match the expected type (422 Unprocessable Content).
+    Data(String),
+    /// A parameter of the route is missing or it doesn't match the expected type (404 Not Found).
+    Param(String),
+}
+impl ExtractError {
+    /// Returns the status code of the response for the error.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::extract::ExtractError;
+    ///
+    /// let status: i32 = ExtractError::Data("missing field `name`".to_string()).get_status();
+    /// # assert_eq!(status, 422)
+    /// ```
+    pub fn get_status(&self) -> i32 {
+        match self {
+            ExtractError::ContentType(_) | ExtractError::Syntax(_) => 400,
+            ExtractError::Data(_) => 422,
+            ExtractError::Param(_) => 404,
+        }
+    }
+    /// Returns the message of the error.
+    pub fn get_message(&self) -> &str {
+        match self {
+            ExtractError::ContentType(message)
+            | ExtractError::Syntax(message)
+            | ExtractError::Data(message)
+            | ExtractError::Param(message) => message,
+        }
+    }
+}
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Request data cannot be extracted: {}",
+            self.get_message()
+        )
+    }
+}
+impl std::error::Error for ExtractError {}
+impl From<ExtractError> for String {
+    fn from(e: ExtractError) -> Self {
+        e.to_string()
+    }
+}
+impl From<serde_json::Error> for ExtractError {
+    fn from(e: serde_json::Error) -> Self {
+        match e.classify() {
+            serde_json::error::Category::Data => ExtractError::Data(e.to_string()),
+            _ => ExtractError::Syntax(e.to_string()),
+        }
+    }
+}
+impl From<serde_urlencoded::de::Error> for ExtractError {
+    fn from(e: serde_urlencoded::de::Error) -> Self {
+        ExtractError::Data(e.to_string())
+    }
+}
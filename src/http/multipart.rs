@@ -0,0 +1,347 @@
+//! Module for the streaming parser of `multipart/form-data` bodies ([RFC 7578](https://www.rfc-editor.org/rfc/rfc7578)).
+
+use urlencoding::decode;
+
+/// The events raised by the `Parser` while the body is fed.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Event<'b> {
+    /// A new part starts with the given headers.
+    Part(Part),
+    /// Content of the current part found at the given position of the body.
+    Data(&'b [u8], usize),
+    /// The current part ends.
+    End,
+}
+/// The headers of a part of the body.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Part {
+    pub(crate) name: String,
+    pub(crate) filename: Option<String>,
+    pub(crate) content_type: String,
+}
+#[derive(Debug, PartialEq)]
+enum State {
+    Preamble,
+    Delimiter,
+    Headers,
+    Content,
+    Done,
+}
+/// The parser of a `multipart/form-data` body which can be fed in chunks of any size.
+pub(crate) struct Parser {
+    delimiter: Vec<u8>,
+    buffer: Vec<u8>,
+    offset: usize,
+    state: State,
+}
+impl Parser {
+    /// Creates and returns an instance of `Parser` for the given boundary.
+    pub(crate) fn new(boundary: &str) -> Parser {
+        Parser {
+            delimiter: format!("\r\n--{}", boundary).into_bytes(),
+            // The first delimiter is not preceded by a line break.
+            buffer: b"\r\n".to_vec(),
+            offset: 0,
+            state: State::Preamble,
+        }
+    }
+    /// Parses the given chunk of the body and passes the events to the handler.
+    /// # Errors
+    /// Returns the error message if the body is malformed or the error of the handler if it fails.
+    pub(crate) fn feed<F, E>(&mut self, chunk: &[u8], handler: &mut F) -> Result<(), E>
+    where
+        F: FnMut(Event) -> Result<(), E>,
+        E: From<String>,
+    {
+        self.buffer.extend_from_slice(chunk);
+        let mut start = 0;
+        loop {
+            let buffer = &self.buffer[start..];
+            match self.state {
+                State::Preamble => match find(buffer, &self.delimiter) {
+                    Some(pos) => {
+                        start += pos + self.delimiter.len();
+                        self.state = State::Delimiter;
+                    }
+                    None => {
+                        start += buffer.len().saturating_sub(self.delimiter.len() - 1);
+                        break;
+                    }
+                },
+                State::Delimiter => {
+                    if buffer.starts_with(b"--") {
+                        self.state = State::Done;
+                    } else if let Some(pos) = find(buffer, b"\r\n") {
+                        if buffer[..pos].iter().any(|c| *c != b' ' && *c != b'\t') {
+                            return Err(E::from(
+                                "Multipart delimiter is not followed by a line break.".to_string(),
+                            ));
+                        }
+                        start += pos + 2;
+                        self.state = State::Headers;
+                    } else {
+                        break;
+                    }
+                }
+                State::Headers => {
+                    let end = if buffer.starts_with(b"\r\n") {
+                        Some(0)
+                    } else {
+                        find(buffer, b"\r\n\r\n").map(|pos| pos + 2)
+                    };
+                    match end {
+                        Some(end) => {
+                            let part = parse_part(&String::from_utf8_lossy(&buffer[..end]))
+                                .map_err(E::from)?;
+                            start += end + 2;
+                            self.state = State::Content;
+                            handler(Event::Part(part))?;
+                        }
+                        None => break,
+                    }
+                }
+                State::Content => {
+                    // The line break added before the first delimiter is not part of the body.
+                    let data_offset = self.offset + start - 2;
+                    match find(buffer, &self.delimiter) {
+                        Some(pos) => {
+                            if pos > 0 {
+                                handler(Event::Data(&buffer[..pos], data_offset))?;
+                            }
+                            handler(Event::End)?;
+                            start += pos + self.delimiter.len();
+                            self.state = State::Delimiter;
+                        }
+                        None => {
+                            // The end of the buffer may be the beginning of the delimiter.
+                            let length = buffer.len().saturating_sub(self.delimiter.len() - 1);
+                            if length > 0 {
+                                handler(Event::Data(&buffer[..length], data_offset))?;
+                            }
+                            start += length;
+                            break;
+                        }
+                    }
+                }
+                State::Done => {
+                    start = self.buffer.len();
+                    break;
+                }
+            }
+        }
+        self.buffer.drain(..start);
+        self.offset += start;
+        Ok(())
+    }
+    /// Checks that the whole body has been parsed.
+    /// # Errors
+    /// Returns the error message if the closing delimiter has not been found.
+    pub(crate) fn finish(&self) -> Result<(), String> {
+        if self.state == State::Done {
+            Ok(())
+        } else {
+            Err("Multipart body is incomplete.".to_string())
+        }
+    }
+}
+/// Returns the boundary of the given `multipart/form-data` content type (if it is).
+pub(crate) fn get_boundary(content_type: &str) -> Option<String> {
+    let mut parameters = split_parameters(content_type).into_iter();
+    if !parameters
+        .next()?
+        .trim()
+        .eq_ignore_ascii_case("multipart/form-data")
+    {
+        return None;
+    }
+    parameters
+        .filter_map(|parameter| parse_parameter(&parameter))
+        .find(|(name, _)| name == "boundary")
+        .map(|(_, value)| value)
+        .filter(|boundary| !boundary.is_empty())
+}
+/// Parses the headers of a part of the body.
+fn parse_part(headers: &str) -> Result<Part, String> {
+    let mut disposition = None;
+    let mut content_type = "text/plain".to_string();
+    for line in headers.split("\r\n") {
+        if let Some(pos) = line.find(':') {
+            let value = line[pos + 1..].trim();
+            match line[..pos].trim().to_lowercase().as_str() {
+                "content-disposition" => disposition = Some(value.to_string()),
+                "content-type" => content_type = value.to_string(),
+                _ => (),
+            }
+        }
+    }
+    let disposition =
+        disposition.ok_or_else(|| "Multipart part without Content-Disposition.".to_string())?;
+    let mut parameters = split_parameters(&disposition).into_iter();
+    let mut name = None;
+    let mut filename = None;
+    let mut extended_filename = None;
+    if !parameters
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .eq_ignore_ascii_case("form-data")
+    {
+        return Err(format!("Multipart part is not form-data: {}", &disposition));
+    }
+    for (key, value) in parameters.filter_map(|parameter| parse_parameter(&parameter)) {
+        match key.as_str() {
+            "name" => name = Some(value),
+            "filename" => filename = Some(value),
+            "filename*" => extended_filename = decode_extended_value(&value),
+            _ => (),
+        }
+    }
+    Ok(Part {
+        name: name.ok_or_else(|| format!("Multipart part without name: {}", &disposition))?,
+        filename: extended_filename
+            .or(filename)
+            .map(|filename| get_basename(&filename)),
+        content_type,
+    })
+}
+/// Splits the header value by the semicolons which are not quoted.
+fn split_parameters(value: &str) -> Vec<String> {
+    let mut parameters = Vec::new();
+    let mut parameter = String::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    for c in value.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                parameters.push(std::mem::take(&mut parameter));
+                continue;
+            }
+            _ => (),
+        }
+        parameter.push(c);
+    }
+    parameters.push(parameter);
+    parameters
+}
+/// Returns the lowercase name and the unquoted value of the given `name=value` parameter.
+fn parse_parameter(parameter: &str) -> Option<(String, String)> {
+    let pos = parameter.find('=')?;
+    let name = parameter[..pos].trim().to_lowercase();
+    let value = parameter[pos + 1..].trim();
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        let mut unquoted = String::new();
+        let mut chars = value[1..value.len() - 1].chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => unquoted.extend(chars.next()),
+                c => unquoted.push(c),
+            }
+        }
+        Some((name, unquoted))
+    } else {
+        Some((name, value.to_string()))
+    }
+}
+/// Decodes a value written as `charset'language'percent-encoded-value` ([RFC 5987](https://www.rfc-editor.org/rfc/rfc5987)).
+fn decode_extended_value(value: &str) -> Option<String> {
+    let mut chunks = value.splitn(3, '\'');
+    let charset = chunks.next()?;
+    let encoded = chunks.nth(1)?;
+    if charset.eq_ignore_ascii_case("UTF-8") {
+        decode(encoded).ok().map(|decoded| decoded.to_string())
+    } else {
+        None
+    }
+}
+/// Returns the file name without the directories some browsers send with it.
+fn get_basename(filename: &str) -> String {
+    match filename.rfind(&['/', '\\'][..]) {
+        Some(pos) => filename[pos + 1..].to_string(),
+        None => filename.to_string(),
+    }
+}
+/// Returns the position of the first occurrence of the needle in the haystack.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &[u8] = b"preamble\r\n--XyZ\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nHello\r\n--XyZ\r\nContent-Disposition: form-data; name=\"file\"; filename=\"my photo; 1.png\"\r\nContent-Type: image/png\r\n\r\n\x89PNG\r\n\x00\xff\r\n--X\r\n--XyZ--\r\nepilogue";
+
+    fn parse(chunk_size: usize) -> Vec<(Part, Vec<u8>)> {
+        let mut parts: Vec<(Part, Vec<u8>)> = Vec::new();
+        let mut parser = Parser::new("XyZ");
+        for chunk in BODY.chunks(chunk_size) {
+            parser
+                .feed(chunk, &mut |event| {
+                    match event {
+                        Event::Part(part) => parts.push((part, Vec::new())),
+                        Event::Data(data, offset) => {
+                            let content = &mut parts.last_mut().unwrap().1;
+                            assert_eq!(&BODY[offset..offset + data.len()], data);
+                            content.extend_from_slice(data);
+                        }
+                        Event::End => (),
+                    }
+                    Ok::<(), String>(())
+                })
+                .unwrap();
+        }
+        parser.finish().unwrap();
+        parts
+    }
+
+    #[test]
+    fn test_feed() {
+        for chunk_size in &[1, 3, 7, BODY.len()] {
+            let parts = parse(*chunk_size);
+            assert_eq!(parts.len(), 2);
+            assert_eq!(parts[0].0.name, "title");
+            assert_eq!(parts[0].0.filename, None);
+            assert_eq!(parts[0].1, b"Hello");
+            assert_eq!(parts[1].0.filename, Some("my photo; 1.png".to_string()));
+            assert_eq!(parts[1].0.content_type, "image/png");
+            assert_eq!(parts[1].1, b"\x89PNG\r\n\x00\xff\r\n--X");
+        }
+    }
+    #[test]
+    fn test_finish_with_incomplete_body() {
+        let mut parser = Parser::new("XyZ");
+        parser
+            .feed(&BODY[..BODY.len() - 20], &mut |_| Ok::<(), String>(()))
+            .unwrap();
+        assert!(parser.finish().is_err());
+    }
+    #[test]
+    fn test_get_boundary() {
+        assert_eq!(
+            get_boundary("multipart/form-data; boundary=\"a b;c\""),
+            Some("a b;c".to_string())
+        );
+        assert_eq!(
+            get_boundary("Multipart/Form-Data;Boundary=----WebKit123"),
+            Some("----WebKit123".to_string())
+        );
+        assert_eq!(get_boundary("application/json"), None);
+    }
+    #[test]
+    fn test_parse_part() {
+        let part = parse_part("Content-Disposition: form-data; name=\"doc\"; filename=\"C:\\\\tmp\\\\cv.pdf\"; filename*=UTF-8''curr%C3%ADculum.pdf").unwrap();
+        assert_eq!(part.name, "doc");
+        assert_eq!(part.filename, Some("currículum.pdf".to_string()));
+        let part = parse_part(
+            "content-disposition: form-data; name=\"a\"; filename=\"C:\\\\tmp\\\\cv.pdf\"",
+        )
+        .unwrap();
+        assert_eq!(part.filename, Some("cv.pdf".to_string()));
+        assert!(parse_part("Content-Type: text/plain").is_err());
+    }
+}
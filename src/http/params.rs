@@ -0,0 +1,153 @@
+//! Module for the nested view of the form and query data of the request object.
+
+use serde_json::{Map, Value};
+
+/// Returns the nested structure of the given parameters written in bracket notation.
+/// `user[address][city]=Paris` becomes an object, `tags[]=a&tags[]=b` (or a repeated `tags`) becomes an array,
+/// and objects with consecutive numeric keys (`rows[0][name]`, `rows[1][name]`...) become arrays as well.
+pub(crate) fn nest(pairs: &[(String, String)]) -> Value {
+    let mut root = Value::Object(Map::new());
+    for (key, value) in pairs {
+        insert(&mut root, &parse_key(key), Value::String(value.to_string()));
+    }
+    into_arrays(root)
+}
+/// Splits the key written in bracket notation into its segments (`None` for `[]`).
+fn parse_key(key: &str) -> Vec<Option<String>> {
+    let (name, mut rest) = match key.find('[') {
+        Some(pos) if pos > 0 && key.ends_with(']') => (&key[..pos], &key[pos..]),
+        _ => return vec![Some(key.to_string())],
+    };
+    let mut segments = vec![Some(name.to_string())];
+    while let Some(end) = rest.find(']') {
+        if !rest.starts_with('[') {
+            return vec![Some(key.to_string())];
+        }
+        let segment = &rest[1..end];
+        segments.push(if segment.is_empty() {
+            None
+        } else {
+            Some(segment.to_string())
+        });
+        rest = &rest[end + 1..];
+    }
+    segments
+}
+/// Inserts the value in the given path of segments, creating the objects and arrays on the way.
+fn insert(node: &mut Value, segments: &[Option<String>], value: Value) {
+    let (segment, rest) = match segments.split_first() {
+        Some(x) => x,
+        None => return append(node, value),
+    };
+    match segment {
+        Some(key) => {
+            if !node.is_object() {
+                *node = Value::Object(Map::new());
+            }
+            let child = node
+                .as_object_mut()
+                .unwrap()
+                .entry(key.to_string())
+                .or_insert(Value::Null);
+            if rest.is_empty() {
+                append(child, value);
+            } else {
+                insert(child, rest, value);
+            }
+        }
+        None => {
+            if !node.is_array() {
+                *node = Value::Array(Vec::new());
+            }
+            let array = node.as_array_mut().unwrap();
+            if rest.is_empty() {
+                array.push(value);
+            } else {
+                array.push(Value::Null);
+                insert(array.last_mut().unwrap(), rest, value);
+            }
+        }
+    }
+}
+/// Sets the value of the node, turning it into an array if it already has a value.
+fn append(node: &mut Value, value: Value) {
+    match node {
+        Value::Null => *node = value,
+        Value::Array(array) => array.push(value),
+        _ => {
+            let previous = node.take();
+            *node = Value::Array(vec![previous, value]);
+        }
+    }
+}
+/// Turns the objects with consecutive numeric keys starting from 0 into arrays.
+fn into_arrays(node: Value) -> Value {
+    match node {
+        Value::Object(mut map) => {
+            let is_list =
+                !map.is_empty() && (0..map.len()).all(|index| map.contains_key(&index.to_string()));
+            if is_list {
+                Value::Array(
+                    (0..map.len())
+                        .map(|index| into_arrays(map.remove(&index.to_string()).unwrap()))
+                        .collect(),
+                )
+            } else {
+                Value::Object(
+                    map.into_iter()
+                        .map(|(key, value)| (key, into_arrays(value)))
+                        .collect(),
+                )
+            }
+        }
+        Value::Array(array) => Value::Array(array.into_iter().map(into_arrays).collect()),
+        value => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn pairs(data: &[(&str, &str)]) -> Vec<(String, String)> {
+        data.iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_nest() {
+        let value = nest(&pairs(&[
+            ("user[name]", "John"),
+            ("user[address][city]", "Paris"),
+            ("tags[]", "a"),
+            ("tags[]", "b"),
+            ("colors", "red"),
+            ("colors", "blue"),
+            ("rows[0][id]", "1"),
+            ("rows[1][id]", "2"),
+            ("page", "2"),
+        ]));
+        assert_eq!(
+            value,
+            json!({
+                "user": {"name": "John", "address": {"city": "Paris"}},
+                "tags": ["a", "b"],
+                "colors": ["red", "blue"],
+                "rows": [{"id": "1"}, {"id": "2"}],
+                "page": "2"
+            })
+        );
+    }
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(parse_key("name"), vec![Some("name".to_string())]);
+        assert_eq!(
+            parse_key("a[b][]"),
+            vec![Some("a".to_string()), Some("b".to_string()), None]
+        );
+        assert_eq!(parse_key("[a]"), vec![Some("[a]".to_string())]);
+        assert_eq!(parse_key("a[b]c]"), vec![Some("a[b]c]".to_string())]);
+    }
+}
@@ -0,0 +1,248 @@
+//! Module for the forwarding headers sent by the reverse proxies listed in `server.trusted_proxies`.
+
+use std::net::IpAddr;
+
+/// Checks whether the ip address matches any of the given proxies (ip addresses or CIDR ranges such as `10.0.0.0/8`).
+pub(crate) fn is_trusted(ip: IpAddr, proxies: &[String]) -> bool {
+    proxies.iter().any(|proxy| matches(ip, proxy.trim()))
+}
+/// Returns the ip address of the client walking the forwarding chain from the nearest proxy
+/// until an address which is not a trusted proxy is found.
+/// `Forwarded` takes precedence over `X-Forwarded-For`, which takes precedence over `X-Real-IP`.
+pub(crate) fn get_client_ip(
+    remote_ip: IpAddr,
+    headers: &[(String, String)],
+    proxies: &[String],
+) -> IpAddr {
+    if !is_trusted(remote_ip, proxies) {
+        return remote_ip;
+    }
+    let mut chain = get_forwarded(headers, "for");
+    if chain.is_empty() {
+        chain = get_values(headers, "X-Forwarded-For");
+    }
+    if chain.is_empty() {
+        chain = get_values(headers, "X-Real-IP");
+    }
+    let mut client_ip = remote_ip;
+    for node in chain.iter().rev() {
+        match parse_node(node) {
+            Some(ip) => {
+                client_ip = ip;
+                if !is_trusted(ip, proxies) {
+                    break;
+                }
+            }
+            // Obfuscated or unknown nodes end the chain.
+            None => break,
+        }
+    }
+    client_ip
+}
+/// Returns the scheme requested by the client to the outermost trusted proxy (`Forwarded: proto` or `X-Forwarded-Proto`).
+pub(crate) fn get_forwarded_proto(
+    remote_ip: IpAddr,
+    headers: &[(String, String)],
+    proxies: &[String],
+) -> Option<String> {
+    get_forwarded_value(remote_ip, headers, proxies, "proto", "X-Forwarded-Proto")
+        .map(|proto| proto.to_lowercase())
+}
+/// Returns the host requested by the client to the outermost trusted proxy (`Forwarded: host` or `X-Forwarded-Host`).
+pub(crate) fn get_forwarded_host(
+    remote_ip: IpAddr,
+    headers: &[(String, String)],
+    proxies: &[String],
+) -> Option<String> {
+    get_forwarded_value(remote_ip, headers, proxies, "host", "X-Forwarded-Host")
+}
+/// Returns the value set by the outermost trusted proxy walking the forwarding chain from the nearest proxy,
+/// so the values sent by the client (on the left of the chain) are ignored.
+fn get_forwarded_value(
+    remote_ip: IpAddr,
+    headers: &[(String, String)],
+    proxies: &[String],
+    parameter: &str,
+    header: &str,
+) -> Option<String> {
+    if !is_trusted(remote_ip, proxies) {
+        return None;
+    }
+    let elements = get_values(headers, "Forwarded");
+    if !elements.is_empty() {
+        // Each element is added by a proxy with the node it received the request from.
+        let mut value = None;
+        for element in elements.iter().rev() {
+            if let Some(element_value) = get_parameter(element, parameter) {
+                value = Some(element_value);
+            }
+            match get_parameter(element, "for").and_then(|node| parse_node(&node)) {
+                Some(ip) if is_trusted(ip, proxies) => continue,
+                _ => break,
+            }
+        }
+        return value;
+    }
+    // Each proxy appends a value, the nearest one being the last.
+    // `hops` is never 0: the value appended by the peer itself is always read.
+    let hops = 1 + get_values(headers, "X-Forwarded-For")
+        .iter()
+        .rev()
+        .take_while(|node| matches!(parse_node(node), Some(ip) if is_trusted(ip, proxies)))
+        .count();
+    get_values(headers, header).into_iter().rev().nth(hops - 1)
+}
+/// Returns the values of the given parameter of the `Forwarded` headers ([RFC 7239](https://www.rfc-editor.org/rfc/rfc7239)) in order.
+fn get_forwarded(headers: &[(String, String)], parameter: &str) -> Vec<String> {
+    get_values(headers, "Forwarded")
+        .iter()
+        .filter_map(|element| get_parameter(element, parameter))
+        .collect()
+}
+/// Returns the value of the given parameter of an element of the `Forwarded` header.
+fn get_parameter(element: &str, parameter: &str) -> Option<String> {
+    element.split(';').find_map(|pair| {
+        let pos = pair.find('=')?;
+        if pair[..pos].trim().eq_ignore_ascii_case(parameter) {
+            Some(pair[pos + 1..].trim().trim_matches('"').to_string())
+        } else {
+            None
+        }
+    })
+}
+/// Returns the comma separated values of all the headers with the given name in order.
+fn get_values(headers: &[(String, String)], name: &str) -> Vec<String> {
+    headers
+        .iter()
+        .filter(|(header, _)| header.eq_ignore_ascii_case(name))
+        .flat_map(|(_, value)| value.split(','))
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+/// Parses the ip address of a node such as `192.0.2.43`, `192.0.2.43:47011` or `[2001:db8::1]:4711`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    if let Ok(ip) = node.parse() {
+        return Some(ip);
+    }
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split(']').next()?.parse().ok();
+    }
+    node.rsplit_once(':')?.0.parse().ok()
+}
+/// Checks whether the ip address matches the given ip address or CIDR range.
+fn matches(ip: IpAddr, proxy: &str) -> bool {
+    let (address, prefix) = match proxy.find('/') {
+        Some(pos) => (&proxy[..pos], proxy[pos + 1..].parse::<u32>().ok()),
+        None => (proxy, None),
+    };
+    match (ip, address.parse::<IpAddr>()) {
+        (IpAddr::V4(ip), Ok(IpAddr::V4(network))) => {
+            let prefix = prefix.unwrap_or(32).min(32);
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), Ok(IpAddr::V6(network))) => {
+            let prefix = prefix.unwrap_or(128).min(128);
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(data: &[(&str, &str)]) -> Vec<(String, String)> {
+        data.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_is_trusted() {
+        let proxies = vec!["10.0.0.0/8".to_string(), "::1".to_string()];
+        assert!(is_trusted("10.1.2.3".parse().unwrap(), &proxies));
+        assert!(is_trusted("::1".parse().unwrap(), &proxies));
+        assert!(!is_trusted("11.0.0.1".parse().unwrap(), &proxies));
+        assert!(!is_trusted("127.0.0.1".parse().unwrap(), &proxies));
+    }
+    #[test]
+    fn test_get_client_ip() {
+        let proxies = vec!["10.0.0.0/8".to_string()];
+        let proxy = "10.0.0.1".parse().unwrap();
+        let forwarded_for = headers(&[("X-Forwarded-For", "1.1.1.1, 2.2.2.2, 10.0.0.2")]);
+        assert_eq!(
+            get_client_ip(proxy, &forwarded_for, &proxies),
+            "2.2.2.2".parse::<IpAddr>().unwrap()
+        );
+        let forwarded = headers(&[
+            ("Forwarded", "for=\"[2001:db8::1]:4711\";proto=https"),
+            ("X-Forwarded-For", "3.3.3.3"),
+        ]);
+        assert_eq!(
+            get_client_ip(proxy, &forwarded, &proxies),
+            "2001:db8::1".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(
+            get_client_ip(proxy, &headers(&[("X-Real-IP", "4.4.4.4")]), &proxies),
+            "4.4.4.4".parse::<IpAddr>().unwrap()
+        );
+        let client = "5.5.5.5".parse().unwrap();
+        assert_eq!(get_client_ip(client, &forwarded_for, &proxies), client);
+    }
+    #[test]
+    fn test_get_forwarded_proto_and_host() {
+        let proxies = vec!["10.0.0.0/8".to_string()];
+        let proxy = "10.0.0.1".parse().unwrap();
+        let forwarded = headers(&[("Forwarded", "for=1.1.1.1;proto=HTTPS;host=example.com")]);
+        assert_eq!(
+            get_forwarded_proto(proxy, &forwarded, &proxies),
+            Some("https".to_string())
+        );
+        assert_eq!(
+            get_forwarded_host(proxy, &forwarded, &proxies),
+            Some("example.com".to_string())
+        );
+        let client = "1.1.1.1".parse().unwrap();
+        assert_eq!(get_forwarded_host(client, &forwarded, &proxies), None);
+        let forwarded = headers(&[("X-Forwarded-Proto", "https")]);
+        assert_eq!(
+            get_forwarded_proto(proxy, &forwarded, &proxies),
+            Some("https".to_string())
+        );
+        assert_eq!(get_forwarded_host(proxy, &forwarded, &proxies), None);
+    }
+    #[test]
+    fn test_get_forwarded_proto_and_host_ignore_spoofed_values() {
+        let proxies = vec!["10.0.0.0/8".to_string()];
+        let proxy = "10.0.0.1".parse().unwrap();
+        let forwarded = headers(&[(
+            "Forwarded",
+            "for=6.6.6.6;host=evil.com;proto=https, for=1.1.1.1;host=example.com;proto=http, for=10.0.0.2",
+        )]);
+        assert_eq!(
+            get_forwarded_host(proxy, &forwarded, &proxies),
+            Some("example.com".to_string())
+        );
+        assert_eq!(
+            get_forwarded_proto(proxy, &forwarded, &proxies),
+            Some("http".to_string())
+        );
+        let forwarded = headers(&[
+            ("X-Forwarded-For", "6.6.6.6, 1.1.1.1, 10.0.0.2"),
+            ("X-Forwarded-Host", "evil.com, example.com, backend"),
+            ("X-Forwarded-Proto", "https, http, http"),
+        ]);
+        assert_eq!(
+            get_forwarded_host(proxy, &forwarded, &proxies),
+            Some("example.com".to_string())
+        );
+        assert_eq!(
+            get_forwarded_proto(proxy, &forwarded, &proxies),
+            Some("http".to_string())
+        );
+    }
+}
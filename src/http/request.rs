@@ -1,54 +1,248 @@
 //! Module for the request object passed to the handler.
 
-use log::warn;
+use bytes::Bytes;
+use crate::{
+    http::{
+        extensions::Extensions, extract::ExtractError, multipart, params, proxy, upload::Upload,
+    },
+    settings,
+};
 use kalgan_router::Route;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use log::warn;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fmt,
+    fs,
+    io::{self, Read},
+    net::{IpAddr, SocketAddr},
+    path::Path,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+use tempfile::TempPath;
 use urlencoding::decode;
 
+/// The input data and the files sent in the body of the request.
+type FormData = (Vec<(String, String)>, HashMap<String, File>);
+/// The function which reads a forwarding header sent by one of the trusted proxies.
+type ForwardedReader = fn(IpAddr, &[(String, String)], &[String]) -> Option<String>;
+/// The extraction error raised while the request is handled, which is not passed on to the clones of the request.
+#[derive(Debug, Default)]
+struct Rejection(Mutex<Option<ExtractError>>);
+impl Clone for Rejection {
+    fn clone(&self) -> Self {
+        Rejection::default()
+    }
+}
 #[derive(Serialize, Deserialize, Debug, Clone)]
 /// The struct that contains all the data of the file attached to the request.
-pub struct File<'a> {
+/// Uploaded files bigger than `server.upload.spool_threshold` are written to a temporary file,
+/// which is removed when the request is dropped unless it's persisted.
+pub struct File {
     pub filename: String,
     pub content_type: String,
-    pub content: &'a [u8],
+    size: u64,
+    #[serde(skip)]
+    content: Content,
+}
+/// Where the content of the file is kept.
+#[derive(Debug, Clone)]
+enum Content {
+    Memory(Bytes),
+    Disk(Arc<TempPath>),
+}
+impl Default for Content {
+    fn default() -> Self {
+        Content::Memory(Bytes::new())
+    }
+}
+impl File {
+    /// Creates and returns an instance of the ´File´ struct with the given content.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::File;
+    ///
+    /// let file = File::new("avatar.png".to_string(), "image/png".to_string(), vec![137, 80, 78, 71]);
+    /// # assert_eq!(file.size(), 4)
+    /// ```
+    pub fn new(filename: String, content_type: String, content: Vec<u8>) -> File {
+        File::from_bytes(filename, content_type, Bytes::from(content))
+    }
+    /// Creates and returns a `File` with the given content, which may share its memory with the body of the request.
+    pub(crate) fn from_bytes(filename: String, content_type: String, content: Bytes) -> File {
+        File {
+            filename,
+            content_type,
+            size: content.len() as u64,
+            content: Content::Memory(content),
+        }
+    }
+    /// Creates and returns a `File` whose content has been written to the given temporary file.
+    pub(crate) fn from_temp(filename: String, content_type: String, path: TempPath, size: usize) -> File {
+        File {
+            filename,
+            content_type,
+            size: size as u64,
+            content: Content::Disk(Arc::new(path)),
+        }
+    }
+    /// Returns the size of the file in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+    /// Returns the path of the temporary file if the file has been written to disk.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::File;
+    ///
+    /// let file = File::new("notes.txt".to_string(), "text/plain".to_string(), b"foo".to_vec());
+    /// # assert!(file.path().is_none())
+    /// ```
+    pub fn path(&self) -> Option<&Path> {
+        match &self.content {
+            Content::Memory(_) => None,
+            Content::Disk(path) => Some(path),
+        }
+    }
+    /// Returns the content of the file if it's kept in memory, use `reader` to read it anyway.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::File;
+    ///
+    /// let file = File::new("notes.txt".to_string(), "text/plain".to_string(), b"foo".to_vec());
+    /// let content: Option<&[u8]> = file.content();
+    /// # assert_eq!(content, Some(&b"foo"[..]))
+    /// ```
+    pub fn content(&self) -> Option<&[u8]> {
+        match &self.content {
+            Content::Memory(content) => Some(content),
+            Content::Disk(_) => None,
+        }
+    }
+    /// Returns a reader of the content of the file, wherever it's kept.
+    /// # Errors
+    /// Returns the error of the temporary file if it cannot be opened.
+    /// # Examples
+    /// ```
+    /// use std::io::Read;
+    /// use kalgan::http::request::File;
+    ///
+    /// let file = File::new("notes.txt".to_string(), "text/plain".to_string(), b"foo".to_vec());
+    /// let mut content = String::new();
+    /// file.reader().unwrap().read_to_string(&mut content).unwrap();
+    /// # assert_eq!(content, "foo")
+    /// ```
+    pub fn reader(&self) -> io::Result<Box<dyn Read + '_>> {
+        match &self.content {
+            Content::Memory(content) => Ok(Box::new(&content[..])),
+            Content::Disk(path) => Ok(Box::new(fs::File::open(&**path)?)),
+        }
+    }
+    /// Saves the file in the given path.
+    /// The temporary file is moved when possible, so it cannot be read afterwards.
+    /// # Errors
+    /// Returns the error of the file system.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::File;
+    ///
+    /// let file = File::new("notes.txt".to_string(), "text/plain".to_string(), b"foo".to_vec());
+    /// let dest = std::env::temp_dir().join("kalgan_persist_to_example.txt");
+    /// file.persist_to(&dest).unwrap();
+    /// # assert_eq!(std::fs::read(&dest).unwrap(), b"foo".to_vec());
+    /// # std::fs::remove_file(&dest).unwrap();
+    /// ```
+    pub fn persist_to<P: AsRef<Path>>(&self, dest: P) -> io::Result<()> {
+        match &self.content {
+            Content::Memory(content) => fs::write(dest, content),
+            // The temporary directory may be in another file system.
+            Content::Disk(path) => fs::rename(&**path, &dest).or_else(|_| fs::copy(&**path, &dest).map(|_| ())),
+        }
+    }
 }
 #[derive(Serialize, Deserialize, Debug, Clone)]
 /// The struct that contains all the data sent by the browser.
-pub struct Request<'a> {
+/// It owns all its data, so it can be moved to other threads or kept across `.await` points.
+pub struct Request {
     method: String,
     uri: String,
+    target: String,
+    path: String,
+    query: HashMap<String, String>,
+    query_pairs: Vec<(String, String)>,
     protocol: String,
+    headers: Vec<(String, String)>,
     cookies: HashMap<String, String>,
     host: String,
+    remote_addr: Option<SocketAddr>,
+    secure: bool,
     user_agent: String,
     input: HashMap<String, String>,
+    input_pairs: Vec<(String, String)>,
     referer: String,
-    #[serde(borrow)]
-    files: HashMap<String, File<'a>>,
-    raw: String,
+    files: HashMap<String, File>,
+    #[serde(skip)]
+    body: Bytes,
     pub middleware: HashMap<String, String>,
+    #[serde(skip)]
+    extensions: Extensions,
+    #[serde(skip)]
+    state: Arc<Extensions>,
     pub route: Option<Route>,
+    #[serde(skip)]
+    rejection: Rejection,
 }
-impl<'a> Request<'a> {
+impl Request {
     /// Creates and returns an instance of the ´Request´ struct with the data sent by the browser.
     pub fn new(buffer: &[u8]) -> Option<Request> {
-        let raw = String::from_utf8_lossy(&buffer[..]).to_string();
-        let first_line = raw.split("\r\n").next()?;
+        Request::new_with_upload(buffer, None)
+    }
+    /// Creates and returns an instance of the ´Request´ struct with the header block sent by the browser
+    /// and the `multipart/form-data` body already read by the connection (if any).
+    pub(crate) fn new_with_upload(buffer: &[u8], upload: Option<Upload>) -> Option<Request> {
+        let (head, body) = match buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            Some(pos) => (&buffer[..pos], &buffer[pos + 4..]),
+            None => (buffer, &buffer[buffer.len()..]),
+        };
+        let body = Bytes::copy_from_slice(body);
+        let head = String::from_utf8_lossy(head);
+        let first_line = head.split("\r\n").next()?;
         let mut parameters = first_line.split(" ");
+        let method = parameters.next()?.to_string();
+        let target = parameters.next()?.trim().to_string();
+        let (path, query) = Request::split_target(&target);
+        let headers = Request::parse_headers(&head);
+        let query_pairs = Request::parse_url_encoded_data(query);
+        let (input_pairs, files) = match upload {
+            Some(upload) => (upload.input, upload.files),
+            None => Request::parse_body(&body, &headers),
+        };
         Some(Request {
-            method: parameters.next()?.to_string(),
-            uri: Request::parse_url_encoding(&parameters.next()?.trim().to_string()),
+            method,
+            uri: Request::parse_url_encoding(&target),
+            path: Request::parse_url_encoding(&path.to_string()),
+            target,
+            query: query_pairs.iter().cloned().collect(),
+            query_pairs,
             protocol: parameters.next()?.to_string(),
-            cookies: Request::parse_cookies(&raw),
-            host: Request::parse_host(&raw),
-            user_agent: Request::parse_user_agent(&raw),
-            input: Request::parse_input(&raw),
-            referer: Request::parse_referer(&raw),
-            files: Request::parse_files(&raw, buffer),
-            raw: raw,
+            cookies: Request::parse_cookies(&headers),
+            host: Request::parse_header(&headers, "Host"),
+            remote_addr: None,
+            secure: false,
+            user_agent: Request::parse_header(&headers, "User-Agent"),
+            input: input_pairs.iter().cloned().collect(),
+            input_pairs,
+            referer: Request::parse_header(&headers, "Referer"),
+            headers,
+            files,
+            body,
             middleware: HashMap::new(),
+            extensions: Extensions::new(),
+            state: Arc::default(),
             route: None,
+            rejection: Rejection::default(),
         })
     }
     /// Returns the http method of the request.
@@ -77,6 +271,73 @@ impl<'a> Request<'a> {
     pub fn get_uri(&self) -> &String {
         &self.uri
     }
+    /// Returns the target of the request as it was sent by the browser, without url decoding.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::Request;
+    ///
+    /// let request = Request::new(b"GET /files/a%2Fb?q=%26 HTTP/1.1\r\n\r\n").unwrap();
+    /// let target: &str = request.get_target();
+    /// # assert_eq!(target, "/files/a%2Fb?q=%26");
+    /// # assert_eq!(request.get_uri(), "/files/a/b?q=&")
+    /// ```
+    pub fn get_target(&self) -> &str {
+        &self.target
+    }
+    /// Returns the path of the request (the uri without the query string).
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::Request;
+    /// # use kalgan::http::request::Mock;
+    ///
+    /// # let request = Request::mock().mock_set_uri("/users?page=2".to_string());
+    /// let path: &String = request.get_path();
+    /// # assert_eq!(path, &"/users".to_string())
+    /// ```
+    pub fn get_path(&self) -> &String {
+        &self.path
+    }
+    /// Returns the collection of parameters of the query string of the request.
+    /// # Examples
+    /// ```
+    /// use std::collections::HashMap;
+    /// use kalgan::http::request::Request;
+    /// # use kalgan::http::request::Mock;
+    ///
+    /// # let request = Request::mock().mock_set_uri("/users?page=2&sort=name".to_string());
+    /// let query: &HashMap<String, String> = request.get_query();
+    /// # assert_eq!(query["page"], "2".to_string())
+    /// ```
+    pub fn get_query(&self) -> &HashMap<String, String> {
+        &self.query
+    }
+    /// Returns all the values of the given parameter of the query string of the request.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::Request;
+    /// # use kalgan::http::request::Mock;
+    ///
+    /// # let request = Request::mock().mock_set_uri("/posts?tag=rust&tag=web".to_string());
+    /// let tags: Vec<&str> = request.get_query_all("tag");
+    /// # assert_eq!(tags, vec!["rust", "web"])
+    /// ```
+    pub fn get_query_all(&self, key: &str) -> Vec<&str> {
+        Request::get_values(&self.query_pairs, key)
+    }
+    /// Returns the parameters of the query string of the request as a nested structure.
+    /// See `get_nested_input` for the notation.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::Request;
+    /// # use kalgan::http::request::Mock;
+    ///
+    /// # let request = Request::mock().mock_set_uri("/users?filter[role]=admin&page=2".to_string());
+    /// let query = request.get_nested_query();
+    /// # assert_eq!(query["filter"]["role"], "admin")
+    /// ```
+    pub fn get_nested_query(&self) -> Value {
+        params::nest(&self.query_pairs)
+    }
     /// Returns the http protocol of the request.
     /// # Examples
     /// ```
@@ -90,6 +351,155 @@ impl<'a> Request<'a> {
     pub fn get_protocol(&self) -> &String {
         &self.protocol
     }
+    /// Returns the first value of the given header (case-insensitive) of the request.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::Request;
+    /// # use kalgan::http::request::Mock;
+    ///
+    /// # let request = Request::mock().mock_set_headers(vec![("Accept".to_string(), "text/html".to_string())]);
+    /// let accept: Option<&str> = request.get_header("accept");
+    /// # assert_eq!(accept, Some("text/html"))
+    /// ```
+    pub fn get_header(&self, name: &str) -> Option<&str> {
+        self.get_headers(name).into_iter().next()
+    }
+    /// Returns all the values of the given header (case-insensitive) of the request.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::Request;
+    /// # use kalgan::http::request::Mock;
+    ///
+    /// # let request = Request::mock().mock_set_headers(vec![
+    /// #     ("X-Forwarded-For".to_string(), "10.0.0.1".to_string()),
+    /// #     ("x-forwarded-for".to_string(), "10.0.0.2".to_string()),
+    /// # ]);
+    /// let forwarded_for: Vec<&str> = request.get_headers("X-Forwarded-For");
+    /// # assert_eq!(forwarded_for, vec!["10.0.0.1", "10.0.0.2"])
+    /// ```
+    pub fn get_headers(&self, name: &str) -> Vec<&str> {
+        self.headers
+            .iter()
+            .filter(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+    /// Returns an iterator over the headers of the request (name and value) in the order they were sent.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::Request;
+    /// # use kalgan::http::request::Mock;
+    ///
+    /// # let request = Request::mock().mock_set_headers(vec![("Host".to_string(), "localhost".to_string())]);
+    /// for (name, value) in request.iter_headers() {
+    ///     println!("{}: {}", name, value);
+    /// }
+    /// # assert_eq!(request.iter_headers().count(), 1)
+    /// ```
+    pub fn iter_headers(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+    /// Returns the best of the given content types according to the `Accept` header and its q-values,
+    /// preferring the first one of the list on ties.
+    /// It returns the first content type if the header is not present and `None` if no content type is acceptable.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::Request;
+    /// # use kalgan::http::request::Mock;
+    ///
+    /// # let request = Request::mock().mock_set_headers(vec![(
+    /// #     "Accept".to_string(),
+    /// #     "text/html;q=0.8, application/*".to_string(),
+    /// # )]);
+    /// let content_type: Option<&str> = request.accepts(&["text/html", "application/json"]);
+    /// # assert_eq!(content_type, Some("application/json"))
+    /// ```
+    pub fn accepts<'t>(&self, content_types: &[&'t str]) -> Option<&'t str> {
+        let ranges = self.parse_quality_values("Accept");
+        if ranges.is_empty() {
+            return content_types.first().copied();
+        }
+        let mut best: Option<(&'t str, f32)> = None;
+        for content_type in content_types {
+            // The most specific media range of the header gives the quality of the content type.
+            let quality = ranges
+                .iter()
+                .filter_map(|(range, quality)| {
+                    Request::match_media_range(range, content_type).map(|specificity| (specificity, *quality))
+                })
+                .fold(None, |found: Option<(u8, f32)>, (specificity, quality)| match found {
+                    Some(found) if found.0 >= specificity => Some(found),
+                    _ => Some((specificity, quality)),
+                })
+                .map(|(_, quality)| quality);
+            match (quality, best) {
+                (Some(quality), Some((_, best_quality))) if quality <= best_quality => (),
+                (Some(quality), _) if quality > 0.0 => best = Some((content_type, quality)),
+                _ => (),
+            }
+        }
+        best.map(|(content_type, _)| content_type)
+    }
+    /// Checks whether the request has been sent by javascript (`X-Requested-With: XMLHttpRequest`).
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::Request;
+    /// # use kalgan::http::request::Mock;
+    ///
+    /// # let request = Request::mock().mock_set_headers(vec![("X-Requested-With".to_string(), "XMLHttpRequest".to_string())]);
+    /// let is_ajax: bool = request.is_ajax();
+    /// # assert!(is_ajax)
+    /// ```
+    pub fn is_ajax(&self) -> bool {
+        match self.get_header("X-Requested-With") {
+            Some(value) => value.eq_ignore_ascii_case("XMLHttpRequest"),
+            None => false,
+        }
+    }
+    /// Checks whether the preferred content type of the `Accept` header is json (`application/json` or `+json`).
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::Request;
+    /// # use kalgan::http::request::Mock;
+    ///
+    /// # let request = Request::mock().mock_set_headers(vec![(
+    /// #     "Accept".to_string(),
+    /// #     "application/problem+json, text/html;q=0.9".to_string(),
+    /// # )]);
+    /// let wants_json: bool = request.wants_json();
+    /// # assert!(wants_json)
+    /// ```
+    pub fn wants_json(&self) -> bool {
+        match self.parse_quality_values("Accept").first() {
+            Some((range, quality)) if *quality > 0.0 => {
+                let range = range.to_lowercase();
+                range.ends_with("/json") || range.ends_with("+json")
+            }
+            _ => false,
+        }
+    }
+    /// Returns the languages of the `Accept-Language` header ordered by their q-values (without the not acceptable ones).
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::Request;
+    /// # use kalgan::http::request::Mock;
+    ///
+    /// # let request = Request::mock().mock_set_headers(vec![(
+    /// #     "Accept-Language".to_string(),
+    /// #     "en;q=0.8, es-ES, es;q=0.9, fr;q=0".to_string(),
+    /// # )]);
+    /// let languages: Vec<String> = request.accept_languages();
+    /// # assert_eq!(languages, vec!["es-ES", "es", "en"])
+    /// ```
+    pub fn accept_languages(&self) -> Vec<String> {
+        self.parse_quality_values("Accept-Language")
+            .into_iter()
+            .filter(|(language, quality)| *quality > 0.0 && language != "*")
+            .map(|(language, _)| language)
+            .collect()
+    }
     /// Returns the collection of cookies of the request.
     /// # Examples
     /// ```
@@ -118,6 +528,83 @@ impl<'a> Request<'a> {
     pub fn get_host(&self) -> &String {
         &self.host
     }
+    /// Returns the address of the peer of the connection (the client or the nearest proxy).
+    /// # Examples
+    /// ```
+    /// use std::net::SocketAddr;
+    /// use kalgan::http::request::Request;
+    /// # use kalgan::http::request::Mock;
+    ///
+    /// # let request = Request::mock().mock_set_remote_addr("192.0.2.1:54321".parse().unwrap());
+    /// let remote_addr: Option<SocketAddr> = request.remote_addr();
+    /// # assert_eq!(remote_addr.unwrap().port(), 54321)
+    /// ```
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        self.remote_addr
+    }
+    /// Returns the ip address of the client.
+    /// The `Forwarded`, `X-Forwarded-For` and `X-Real-IP` headers are only taken into account
+    /// when they are sent by one of the proxies of the `server.trusted_proxies` setting.
+    /// # Examples
+    /// ```
+    /// use std::net::IpAddr;
+    /// use kalgan::http::request::Request;
+    /// # use kalgan::http::request::Mock;
+    ///
+    /// # kalgan::mock_settings("tests/mock/settings.yaml");
+    /// # let request = Request::mock()
+    /// #     .mock_set_remote_addr("10.0.0.1:54321".parse().unwrap())
+    /// #     .mock_set_headers(vec![("X-Forwarded-For".to_string(), "192.0.2.43".to_string())]);
+    /// let client_ip: Option<IpAddr> = request.client_ip();
+    /// # assert_eq!(client_ip, "192.0.2.43".parse().ok())
+    /// ```
+    pub fn client_ip(&self) -> Option<IpAddr> {
+        let remote_ip = self.remote_addr?.ip();
+        Some(proxy::get_client_ip(
+            remote_ip,
+            &self.headers,
+            &settings::trusted_proxies(),
+        ))
+    }
+    /// Returns the scheme (`http` or `https`) requested by the client,
+    /// taken from the `Forwarded` or `X-Forwarded-Proto` headers of a trusted proxy.
+    pub fn client_scheme(&self) -> String {
+        self.get_forwarded(proxy::get_forwarded_proto)
+            .unwrap_or_else(|| if self.secure { "https" } else { "http" }.to_string())
+    }
+    /// Returns the host requested by the client,
+    /// taken from the `Forwarded` or `X-Forwarded-Host` headers of a trusted proxy.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::Request;
+    /// # use kalgan::http::request::Mock;
+    ///
+    /// # kalgan::mock_settings("tests/mock/settings.yaml");
+    /// # let request = Request::mock()
+    /// #     .mock_set_remote_addr("10.0.0.1:54321".parse().unwrap())
+    /// #     .mock_set_host("backend:8080".to_string())
+    /// #     .mock_set_headers(vec![(
+    /// #         "Forwarded".to_string(),
+    /// #         "for=192.0.2.43;proto=https;host=example.com".to_string(),
+    /// #     )]);
+    /// let url = format!("{}://{}/", request.client_scheme(), request.client_host());
+    /// # assert_eq!(url, "https://example.com/")
+    /// ```
+    pub fn client_host(&self) -> String {
+        self.get_forwarded(proxy::get_forwarded_host)
+            .unwrap_or_else(|| self.host.to_string())
+    }
+    /// Sets the address of the peer and whether the connection is secure (tls).
+    pub(crate) fn set_peer(&mut self, remote_addr: Option<SocketAddr>, secure: bool) {
+        self.remote_addr = remote_addr;
+        self.secure = secure;
+    }
+    /// Returns the value of the forwarding headers read by the given function
+    /// if the request has been sent by one of the proxies of the `server.trusted_proxies` setting.
+    fn get_forwarded(&self, read: ForwardedReader) -> Option<String> {
+        let remote_ip = self.remote_addr?.ip();
+        read(remote_ip, &self.headers, &settings::trusted_proxies())
+    }
     /// Returns the user agent of the request.
     /// # Examples
     /// ```
@@ -131,7 +618,7 @@ impl<'a> Request<'a> {
     pub fn get_user_agent(&self) -> &String {
         &self.user_agent
     }
-    /// Returns the input data collection of the request.
+    /// Returns the input data collection sent in the body of the request.
     /// # Examples
     /// ```
     /// use std::collections::HashMap;
@@ -147,6 +634,178 @@ impl<'a> Request<'a> {
     pub fn get_input(&self) -> &HashMap<String, String> {
         &self.input
     }
+    /// Returns all the values of the given field of the input data sent in the body of the request (such as a group of checkboxes).
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::Request;
+    ///
+    /// let request = Request::new(b"POST / HTTP/1.1\r\n\r\ntags=a&tags=b").unwrap();
+    /// let tags: Vec<&str> = request.get_all("tags");
+    /// # assert_eq!(tags, vec!["a", "b"])
+    /// ```
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        Request::get_values(&self.input_pairs, key)
+    }
+    /// Returns the input data sent in the body of the request as a nested structure.
+    /// Fields in bracket notation such as `user[address][city]` become objects,
+    /// `tags[]` and repeated fields become arrays and `rows[0][name]`, `rows[1][name]`... become arrays of objects.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::Request;
+    ///
+    /// let request = Request::new(b"POST / HTTP/1.1\r\n\r\nuser[name]=John&user[address][city]=Paris&rows[0][id]=1&rows[1][id]=2").unwrap();
+    /// let input = request.get_nested_input();
+    /// # assert_eq!(input["user"]["address"]["city"], "Paris");
+    /// # assert_eq!(input["rows"][1]["id"], "2")
+    /// ```
+    pub fn get_nested_input(&self) -> Value {
+        params::nest(&self.input_pairs)
+    }
+    /// Returns the value of the given parameter of the route (such as `name` for `/user/{name}`).
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::Request;
+    /// # use kalgan::http::request::Mock;
+    /// # use kalgan_router::Router;
+    ///
+    /// # let mut request = Request::mock();
+    /// # request.route = Router::new("tests/mock/routes.yaml").get_route("/user/John/Doe", "get").ok();
+    /// let name: Option<&str> = request.param("name");
+    /// # assert_eq!(name, Some("John"));
+    /// # assert_eq!(request.param("age"), None)
+    /// ```
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.route.as_ref()?.parameters.get(name).map(|value| value.as_str())
+    }
+    /// Parses the value of the given parameter of the route into the given type.
+    /// # Errors
+    /// Returns `ExtractError::Param` if the route has no such parameter or its value cannot be parsed.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::Request;
+    /// # use kalgan::http::request::Mock;
+    /// # use kalgan_router::Router;
+    ///
+    /// # let mut request = Request::mock();
+    /// # request.route = Router::new("tests/mock/routes.yaml").get_route("/posts/42", "get").ok();
+    /// let id: i64 = request.param_as("id").unwrap();
+    /// # assert_eq!(id, 42)
+    /// ```
+    pub fn param_as<T: FromStr>(&self, name: &str) -> Result<T, ExtractError>
+    where
+        T::Err: fmt::Display,
+    {
+        let result = match self.param(name) {
+            Some(value) => value.parse().map_err(|e| {
+                ExtractError::Param(format!("invalid route parameter `{}` \"{}\": {}", name, value, e))
+            }),
+            None => Err(ExtractError::Param(format!("missing route parameter `{}`", name))),
+        };
+        self.extract(result)
+    }
+    /// Deserializes the url encoded form data sent in the body of the request into the given type.
+    /// Repeated and nested fields are not supported, use `get_all` or `get_nested_input` for them.
+    /// # Errors
+    /// Returns `ExtractError::Data` if the form data doesn't match the type.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::Request;
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Login {
+    ///     username: String,
+    ///     remember: bool,
+    /// }
+    /// let request = Request::new(b"POST / HTTP/1.1\r\n\r\nusername=john&remember=true").unwrap();
+    /// let login: Login = request.form().unwrap();
+    /// # assert_eq!(login.username, "john");
+    /// # assert!(login.remember)
+    /// ```
+    pub fn form<T: DeserializeOwned>(&self) -> Result<T, ExtractError> {
+        self.extract(Request::deserialize_pairs(&self.input_pairs))
+    }
+    /// Deserializes the parameters of the query string of the request into the given type.
+    /// # Errors
+    /// Returns `ExtractError::Data` if the parameters don't match the type.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::Request;
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Pagination {
+    ///     page: u32,
+    ///     per_page: Option<u32>,
+    /// }
+    /// let request = Request::new(b"GET /users?page=2 HTTP/1.1\r\n\r\n").unwrap();
+    /// let pagination: Pagination = request.query().unwrap();
+    /// # assert_eq!(pagination.page, 2);
+    /// # assert_eq!(pagination.per_page, None)
+    /// ```
+    pub fn query<T: DeserializeOwned>(&self) -> Result<T, ExtractError> {
+        self.extract(Request::deserialize_pairs(&self.query_pairs))
+    }
+    /// Deserializes the json body (`application/json` or `application/*+json`) of the request into the given type.
+    /// # Errors
+    /// Returns `ExtractError::ContentType` if the body is not json, `ExtractError::Syntax` if it's malformed
+    /// and `ExtractError::Data` if it doesn't match the type.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::Request;
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct User {
+    ///     name: String,
+    ///     age: u8,
+    /// }
+    /// let request = Request::new(b"POST /users HTTP/1.1\r\nContent-Type: application/json\r\n\r\n{\"name\": \"John\", \"age\": 42}").unwrap();
+    /// let user: User = request.json().unwrap();
+    /// # assert_eq!(user.name, "John");
+    /// # assert_eq!(user.age, 42)
+    /// ```
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, ExtractError> {
+        let content_type = self.get_header("Content-Type").unwrap_or("").to_lowercase();
+        let mime = content_type.split(";").next().unwrap_or("").trim();
+        let result = if mime == "application/json" || (mime.starts_with("application/") && mime.ends_with("+json")) {
+            serde_json::from_slice(&self.body).map_err(ExtractError::from)
+        } else {
+            Err(ExtractError::ContentType(format!("expected application/json, found \"{}\"", mime)))
+        };
+        self.extract(result)
+    }
+    /// Returns the extraction error raised by the controller or middleware (if any) to be answered with its status code.
+    pub(crate) fn take_rejection(&self) -> Option<ExtractError> {
+        self.rejection.0.lock().unwrap().take()
+    }
+    /// Keeps the extraction error (if any) so the handler can answer with its status code and returns the result.
+    fn extract<T>(&self, result: Result<T, ExtractError>) -> Result<T, ExtractError> {
+        if let Err(e) = &result {
+            warn!("{}", e);
+            *self.rejection.0.lock().unwrap() = Some(e.clone());
+        }
+        result
+    }
+    /// Deserializes the collection of parameters into the given type.
+    /// The parameters are encoded again since they are stored decoded.
+    fn deserialize_pairs<T: DeserializeOwned>(pairs: &[(String, String)]) -> Result<T, ExtractError> {
+        let encoded = serde_urlencoded::to_string(pairs).map_err(|e| ExtractError::Syntax(e.to_string()))?;
+        Ok(serde_urlencoded::from_str(&encoded)?)
+    }
+    /// Returns the body of the request as it was sent.
+    /// It's empty for the `multipart/form-data` bodies read by the server, use `get_input` and `get_files` for them.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::Request;
+    ///
+    /// let request = Request::new(b"POST /upload HTTP/1.1\r\nContent-Type: application/octet-stream\r\n\r\n\x00\xff").unwrap();
+    /// let body: &[u8] = request.get_body();
+    /// # assert_eq!(body, &[0, 255])
+    /// ```
+    pub fn get_body(&self) -> &[u8] {
+        &self.body
+    }
     /// Returns the referer field of the request.
     /// # Examples
     /// ```
@@ -170,141 +829,204 @@ impl<'a> Request<'a> {
     /// # use kalgan::http::request::Mock;
     ///
     /// # let mut files_right = HashMap::new();
-    /// # files_right.insert("key".to_string(), File {
-    /// #     filename: "test".to_string(),
-    /// #     content_type: "text/png".to_string(),
-    /// #     content: &[0, 0, 0, 1]
-    /// # });
+    /// # files_right.insert("key".to_string(), File::new("test".to_string(), "text/png".to_string(), vec![0, 0, 0, 1]));
     /// # let request = Request::mock().mock_set_files(files_right.clone());
     /// let files: &HashMap<String, File> = request.get_files();
     /// # assert_eq!(files["key"].filename, files_right["key"].filename);
     /// ```
-    pub fn get_files(&self) -> &HashMap<String, File<'a>> {
+    pub fn get_files(&self) -> &HashMap<String, File> {
         &self.files
     }
-    /// Returns the raw value of the request.
+    /// Returns the typed values attached to the request by the middleware.
     /// # Examples
     /// ```
-    /// use std::collections::HashMap;
     /// use kalgan::http::request::Request;
     /// # use kalgan::http::request::Mock;
     ///
-    /// # let request = Request::mock().mock_set_raw("foobar".to_string());
-    /// let raw: &String = request.get_raw();
-    /// # assert_eq!(raw, &"foobar".to_string());
+    /// struct TenantId(u32);
+    /// # let mut request = Request::mock();
+    /// # request.extensions_mut().insert(TenantId(7));
+    /// let tenant_id: Option<&TenantId> = request.extensions().get::<TenantId>();
+    /// # assert_eq!(tenant_id.unwrap().0, 7)
     /// ```
-    pub fn get_raw(&self) -> &String {
-        &self.raw
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
     }
-    /// Parses and returns the collection of cookies of the request.
-    fn parse_cookies(request: &str) -> HashMap<String, String> {
-        let mut cookies: HashMap<String, String> = HashMap::new();
-        let cookies_collection = match regex::Regex::new(r#"(?i)Cookie:.*"#).unwrap().find(request) {
-            Some(x) => regex::Regex::new(r"(?i)Cookie:").unwrap().replace_all(x.as_str(), "").to_string().replace(" ", ""),
-            None => "".to_string(),
-        };
-        let collection: Vec<&str> = cookies_collection.split(";").collect();
-        for cookie in collection {
-            if cookie.contains("=") {
-                let pos = cookie.find("=").unwrap();
-                let re = regex::Regex::new(r"\s+").unwrap();
-                let cookie_without_spaces = re.replace_all(&cookie, " ").trim().to_string();
-                cookies.insert(
-                    cookie_without_spaces[..pos].to_string(),
-                    cookie_without_spaces[pos + 1..].to_string(),
-                );
-            }
+    /// Returns the typed values attached to the request to add or remove them (such as the authenticated user).
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::Request;
+    /// # use kalgan::http::request::Mock;
+    ///
+    /// struct TenantId(u32);
+    /// # let mut request = Request::mock();
+    /// request.extensions_mut().insert(TenantId(7));
+    /// # assert!(request.extensions().contains::<TenantId>())
+    /// ```
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+    /// Returns the application state of the given type set in the `App` (such as the database pool).
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::Request;
+    /// # use kalgan::http::request::Mock;
+    ///
+    /// struct AppState {
+    ///     name: String,
+    /// }
+    /// # let request = Request::mock().mock_set_state(AppState { name: "kalgan".to_string() });
+    /// let state: Option<&AppState> = request.state::<AppState>();
+    /// # assert_eq!(state.unwrap().name, "kalgan")
+    /// ```
+    pub fn state<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.state.get::<T>()
+    }
+    /// Sets the application state shared by all the requests.
+    pub(crate) fn set_state(&mut self, state: Arc<Extensions>) {
+        self.state = state;
+    }
+    /// Returns the raw value of the request, rebuilt from its request line, headers and body.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::request::Request;
+    /// # use kalgan::http::request::Mock;
+    ///
+    /// # let request = Request::mock()
+    /// #     .mock_set_method("GET".to_string())
+    /// #     .mock_set_uri("/".to_string())
+    /// #     .mock_set_protocol("HTTP/1.1".to_string())
+    /// #     .mock_set_headers(vec![("Host".to_string(), "localhost".to_string())]);
+    /// let raw: String = request.get_raw();
+    /// # assert_eq!(raw, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n".to_string());
+    /// ```
+    pub fn get_raw(&self) -> String {
+        let mut raw = format!("{} {} {}\r\n", self.method, self.target, self.protocol);
+        for (name, value) in &self.headers {
+            raw.push_str(&format!("{}: {}\r\n", name, value));
         }
-        cookies
+        raw.push_str("\r\n");
+        raw.push_str(&String::from_utf8_lossy(&self.body));
+        raw
     }
-    /// Parses and returns the host field of the request.
-    fn parse_host(request: &str) -> String {
-        match regex::Regex::new(r#"(?i)Host:.*"#).unwrap().find(request) {
-            Some(x) => {
-                let mut string = regex::Regex::new(r"(?i)Host:").unwrap().replace_all(x.as_str(), "").to_string().replace(" ", "");
-                let len = string.trim_end_matches(&['\r', '\n'][..]).len();
-                string.truncate(len);
-                Request::parse_url_encoding(&string)
-            }
-            None => "".to_string(),
+    /// Checks whether the connection must be kept alive after the response is sent.
+    /// HTTP/1.1 connections are persistent unless `Connection: close` is sent,
+    /// whereas HTTP/1.0 connections are only kept alive with `Connection: keep-alive`.
+    pub(crate) fn is_keep_alive(&self) -> bool {
+        let connection = self.get_header("Connection").unwrap_or("").to_lowercase();
+        if self.protocol.eq_ignore_ascii_case("HTTP/1.1") {
+            !connection.contains("close")
+        } else {
+            connection.contains("keep-alive")
         }
     }
-    /// Parses and returns the user agent of the request.
-    fn parse_user_agent(request: &str) -> String {
-        match regex::Regex::new(r#"(?i)User-Agent:.*"#).unwrap().find(request) {
-            Some(x) => {
-                let mut string = regex::Regex::new(r"(?i)User-Agent:").unwrap().replace_all(x.as_str(), "").to_string().replace(" ", "");
-                let len = string.trim_end_matches(&['\r', '\n'][..]).len();
-                string.truncate(len);
-                string
+    /// Parses and returns the headers of the request from the header block (after the request line).
+    /// Values folded over several lines are joined with a space.
+    fn parse_headers(header_block: &str) -> Vec<(String, String)> {
+        let mut headers: Vec<(String, String)> = Vec::new();
+        for line in header_block.split("\r\n").skip(1) {
+            if line.starts_with(&[' ', '\t'][..]) {
+                if let Some((_, value)) = headers.last_mut() {
+                    value.push(' ');
+                    value.push_str(line.trim());
+                }
+            } else if let Some(pos) = line.find(':') {
+                headers.push((line[..pos].trim().to_string(), line[pos + 1..].trim().to_string()));
             }
+        }
+        headers
+    }
+    /// Returns the first value of the given header or an empty string if it's not present.
+    fn parse_header(headers: &[(String, String)], name: &str) -> String {
+        match headers.iter().find(|(header, _)| header.eq_ignore_ascii_case(name)) {
+            Some((_, value)) => value.to_string(),
             None => "".to_string(),
         }
     }
-    /// Parses and returns the input data collection of the request.
-    fn parse_input(request: &str) -> HashMap<String, String> {
-        let mut input: HashMap<String, String> = HashMap::new();
-        match regex::Regex::new(r#"(?i)Content-Type: multipart/form-data;"#)
-            .unwrap()
-            .find(request)
-        {
-            Some(_x) => {
-                for mat in regex::Regex::new(r#"(?i)Content-Disposition: form-data; name=.*"#)
-                    .unwrap()
-                    .find_iter(request)
-                {
-                    let mut key = regex::Regex::new(r"(?i)Content-Disposition: form-data; name=").unwrap()
-                        .replace_all(mat.as_str(), "").to_string()
-                        .replace(" ", "")
-                        .replace("\"", "");
-                    let len = key.trim_end_matches(&['\r', '\n'][..]).len();
-                    key.truncate(len);
-                    if !key.contains("filename=") {
-                        let request_clone = request[mat.end()..].to_string().clone();
-                        let lines: Vec<&str> = request_clone.split("\r\n").collect();
-                        input.insert(
-                            Request::parse_url_encoding(&key),
-                            Request::parse_url_encoding(&lines[1].to_string()),
-                        );
-                    }
-                }
-            }
-            None => {
-                let lines: Vec<&str> = request.split("\r\n").collect();
-                let len = lines.len();
-                if lines[len - 2].trim() == "" {
-                    let parameters: Vec<&str> = lines[len - 1].split("&").collect();
-                    for parameter in parameters {
-                        if parameter.contains("=") {
-                            let param: Vec<&str> = parameter.split("=").collect();
-                            input.insert(
-                                Request::parse_url_encoding(&param[0].to_string()),
-                                Request::parse_url_encoding(
-                                    &param[1].to_string().replace("+", " "),
-                                ),
-                            );
-                        }
-                    }
+    /// Parses and returns the collection of cookies of the request.
+    fn parse_cookies(headers: &[(String, String)]) -> HashMap<String, String> {
+        let mut cookies: HashMap<String, String> = HashMap::new();
+        for (_, value) in headers.iter().filter(|(name, _)| name.eq_ignore_ascii_case("Cookie")) {
+            for cookie in value.split(";") {
+                if let Some(pos) = cookie.find("=") {
+                    cookies.insert(cookie[..pos].trim().to_string(), cookie[pos + 1..].trim().to_string());
                 }
             }
         }
-        input
-    }
-    /// Parses and returns the referer field of the request.
-    fn parse_referer(request: &str) -> String {
-        match regex::Regex::new(r#"(?i)Referer:.*"#).unwrap().find(request) {
-            Some(x) => {
-                let mut string = regex::Regex::new(r"(?i)Referer:").unwrap().replace_all(x.as_str(), "").to_string().replace(" ", "");
-                let len = string.trim_end_matches(&['\r', '\n'][..]).len();
-                string.truncate(len);
-                string
+        cookies
+    }
+    /// Returns the comma separated values of the given header with their quality (`q` parameter) from the highest to the lowest.
+    fn parse_quality_values(&self, name: &str) -> Vec<(String, f32)> {
+        let mut values: Vec<(String, f32)> = Vec::new();
+        for item in self.get_headers(name).into_iter().flat_map(|header| header.split(',')) {
+            let mut parameters = item.split(';');
+            let value = parameters.next().unwrap_or_default().trim();
+            if value.is_empty() {
+                continue;
             }
-            None => "".to_string(),
+            let quality = parameters
+                .filter_map(|parameter| {
+                    let pos = parameter.find('=')?;
+                    if parameter[..pos].trim().eq_ignore_ascii_case("q") {
+                        parameter[pos + 1..].trim().parse::<f32>().ok().filter(|quality| quality.is_finite())
+                    } else {
+                        None
+                    }
+                })
+                .next()
+                .unwrap_or(1.0);
+            values.push((value.to_string(), quality.clamp(0.0, 1.0)));
         }
+        // The sort is stable so the values with the same quality keep the order they were sent.
+        values.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        values
+    }
+    /// Returns the specificity of the media range (`*/*`, `type/*` or `type/subtype`) if it matches the content type.
+    fn match_media_range(range: &str, content_type: &str) -> Option<u8> {
+        let (range_type, range_subtype) = range.split_once('/')?;
+        let content_type = content_type.split(';').next().unwrap_or_default().trim();
+        let (main_type, subtype) = content_type.split_once('/')?;
+        if range_type == "*" && range_subtype == "*" {
+            Some(0)
+        } else if !range_type.trim().eq_ignore_ascii_case(main_type) {
+            None
+        } else if range_subtype == "*" {
+            Some(1)
+        } else if range_subtype.trim().eq_ignore_ascii_case(subtype) {
+            Some(2)
+        } else {
+            None
+        }
+    }
+    /// Returns all the values of the given key in the collection of parameters.
+    fn get_values<'b>(pairs: &'b [(String, String)], key: &str) -> Vec<&'b str> {
+        pairs.iter().filter(|(name, _)| name == key).map(|(_, value)| value.as_str()).collect()
+    }
+    /// Splits the request target into the path and the query string.
+    fn split_target(target: &str) -> (&str, &str) {
+        match target.find("?") {
+            Some(pos) => (&target[..pos], &target[pos + 1..]),
+            None => (target, ""),
+        }
+    }
+    /// Parses and returns the collection of parameters of url encoded data (a query string or a form body).
+    fn parse_url_encoded_data(data: &str) -> Vec<(String, String)> {
+        let mut parameters: Vec<(String, String)> = Vec::new();
+        for parameter in data.split("&").filter(|parameter| !parameter.is_empty()) {
+            let (key, value) = match parameter.find("=") {
+                Some(pos) => (&parameter[..pos], &parameter[pos + 1..]),
+                None => (parameter, ""),
+            };
+            parameters.push((
+                Request::parse_url_encoding(&key.replace("+", " ")),
+                Request::parse_url_encoding(&value.replace("+", " ")),
+            ));
+        }
+        parameters
     }
     /// Parses and returns the uri of the request.
     fn parse_url_encoding(url_encoded_string: &String) -> String {
-        match decode(&url_encoded_string) {
+        match decode(url_encoded_string) {
             Ok(s) => s.to_string(),
             Err(e) => {
                 warn!("{}", e);
@@ -312,114 +1034,86 @@ impl<'a> Request<'a> {
             }
         }
     }
-    /// Parses and returns the collection of files attached to the request.
-    fn parse_files<'b>(request: &str, buffer: &'b [u8]) -> HashMap<String, File<'b>> {
+    /// Parses and returns the input data collection and the files sent in the body of the request.
+    /// Url encoded data is parsed when no other content type is given, and `multipart/form-data` part by part
+    /// (parts with a file name are files, the rest are input data).
+    fn parse_body(body: &Bytes, headers: &[(String, String)]) -> FormData {
+        let content_type = Request::parse_header(headers, "Content-Type");
+        match multipart::get_boundary(&content_type) {
+            Some(boundary) => match Request::parse_multipart(body, &boundary) {
+                Ok(data) => data,
+                Err(e) => {
+                    warn!("Error parsing multipart body.");
+                    warn!("{}", e);
+                    (Vec::new(), HashMap::new())
+                }
+            },
+            None => {
+                let content_type = content_type.to_lowercase();
+                if content_type.is_empty() || content_type.starts_with("application/x-www-form-urlencoded") {
+                    (Request::parse_url_encoded_data(String::from_utf8_lossy(body).trim_end()), HashMap::new())
+                } else {
+                    (Vec::new(), HashMap::new())
+                }
+            }
+        }
+    }
+    /// Parses the parts of the `multipart/form-data` body sharing the memory of the content of the files with it.
+    fn parse_multipart(body: &Bytes, boundary: &str) -> Result<FormData, String> {
+        let mut input: Vec<(String, String)> = Vec::new();
         let mut files: HashMap<String, File> = HashMap::new();
-        match regex::Regex::new(r#"(?i)Content-Type: multipart/form-data;.*?\r\n"#)
-            .unwrap()
-            .find(request)
-        {
-            Some(x) => {
-                let param: Vec<&str> = request[x.start()..x.end()]
-                    .trim_end_matches(&['\r', '\n'][..])
-                    .split("; boundary=")
-                    .collect();
-                let boundary = &param[1];
-                let mut prev = 0;
-                for mat in regex::Regex::new(
-                    format!(
-                        r#"(?i)Content-Disposition: form-data; name=(?s:.*?){}"#,
-                        boundary
-                    )
-                        .as_str(),
-                )
-                    .unwrap()
-                    .find_iter(request)
-                {
-                    let request_clone = request[mat.start()..mat.end()].to_string().clone();
-                    let lines: Vec<&str> = request_clone
-                        .trim_start_matches(&['\r', '\n'][..])
-                        .split("\r\n")
-                        .collect();
-                    let first_line = lines[0].to_string();
-                    if first_line.contains("filename=") && !first_line.contains("filename=\"\"") {
-                        let params: Vec<&str> = first_line.split("filename=").collect();
-                        let mut content_type = regex::Regex::new(r"(?i)Content-Type:").unwrap()
-                            .replace_all(lines[1], "").to_string()
-                            .replace(" ", "")
-                            .replace("\"", "");
-                        let len = content_type.trim_end_matches(&['\r', '\n'][..]).len();
-                        content_type.truncate(len);
-                        let x1 = request_clone.find(&lines[3].to_string()).unwrap();
-                        let x2 = request_clone
-                            .find(&lines[lines.len() - 1].to_string())
-                            .unwrap();
-                        let content = request_clone[x1..x2].trim_end_matches(&['\r', '\n'][..]);
-                        let n1 = if prev == 0 {
-                            request.to_string().find(content).unwrap()
-                        } else {
-                            let mut new_n1 = prev.clone();
-                            loop {
-                                if String::from_utf8_lossy(&buffer[new_n1..new_n1 + 4])
-                                    .to_string()
-                                    .find("\r\n\r\n")
-                                    == Some(0)
-                                {
-                                    break new_n1 + 4;
-                                }
-                                new_n1 = new_n1 + 1;
-                            }
-                        };
-                        let mut n2 = n1.clone();
-                        loop {
-                            n2 = n2 + 1;
-                            let test = String::from_utf8_lossy(&buffer[n2 - boundary.len()..n2])
-                                .to_string();
-                            if test.contains(boundary) {
-                                prev = n2.clone();
-                                n2 = n2 - 4 - boundary.len();
-                                break;
+        let mut current: Option<(multipart::Part, usize, usize)> = None;
+        let mut parser = multipart::Parser::new(boundary);
+        parser.feed(body, &mut |event| {
+            match event {
+                multipart::Event::Part(part) => current = Some((part, 0, 0)),
+                multipart::Event::Data(data, offset) => {
+                    if let Some((_, start, end)) = current.as_mut() {
+                        if *start == *end {
+                            *start = offset;
+                        }
+                        *end = offset + data.len();
+                    }
+                }
+                multipart::Event::End => {
+                    if let Some((part, start, end)) = current.take() {
+                        let content = body.slice(start..end);
+                        match part.filename {
+                            // Empty file inputs are sent without file name.
+                            Some(filename) if filename.is_empty() => (),
+                            Some(filename) => {
+                                files.insert(part.name, File::from_bytes(filename, part.content_type, content));
                             }
+                            None => input.push((part.name, String::from_utf8_lossy(&content).to_string())),
                         }
-                        files.insert(
-                            kalgan_string::strip(
-                                kalgan_string::strip_right(
-                                    regex::Regex::new(r"(?i)Content-Disposition: form-data; name=")
-                                        .unwrap().replace_all(params[0], "").trim(),
-                                    ';',
-                                ),
-                                '"',
-                            )
-                                .to_string(),
-                            File {
-                                filename: params[1].replace(" ", "").replace("\"", ""),
-                                content_type: content_type,
-                                content: &buffer[n1..n2],
-                            },
-                        );
                     }
                 }
             }
-            None => (),
-        }
-        files
+            Ok::<(), String>(())
+        })?;
+        parser.finish()?;
+        Ok((input, files))
     }
 }
 #[cfg(feature = "test")]
 /// Describes all the methods to set the `Request` fields to be used in testing.
-pub trait Mock<'a> {
+pub trait Mock {
     /// Creates the `Request` object with empty fields to be used in testing and returns the instance.
     fn mock() -> Self;
     /// Sets the http method of the request and returns the instance.
     fn mock_set_method(self, method: String) -> Self;
-    /// Sets the uri of the request and returns the instance.
+    /// Sets the uri of the request, with its path and query string, and returns the instance.
     fn mock_set_uri(self, uri: String) -> Self;
     /// Sets the protocol of the request and returns the instance.
     fn mock_set_protocol(self, protocol: String) -> Self;
+    /// Sets the headers of the request and returns the instance.
+    fn mock_set_headers(self, headers: Vec<(String, String)>) -> Self;
     /// Sets the collection of cookies of the request and returns the instance.
     fn mock_set_cookies(self, cookies: HashMap<String, String>) -> Self;
     /// Sets the host field of the request and returns the instance.
     fn mock_set_host(self, host: String) -> Self;
+    /// Sets the address of the peer of the connection and returns the instance.
+    fn mock_set_remote_addr(self, remote_addr: SocketAddr) -> Self;
     /// Sets the user agent of the request and returns the instance.
     fn mock_set_user_agent(self, user_agent: String) -> Self;
     /// Sets the input data collection of the request and returns the instance.
@@ -427,26 +1121,39 @@ pub trait Mock<'a> {
     /// Sets the referer field the request and returns the instance.
     fn mock_set_referer(self, referer: String) -> Self;
     /// Sets the collection of files attached to the request and returns the instance.
-    fn mock_set_files(self, files: HashMap<String, File<'a>>) -> Self;
-    /// Sets the raw value of the request and returns the instance.
-    fn mock_set_raw(self, raw: String) -> Self;
+    fn mock_set_files(self, files: HashMap<String, File>) -> Self;
+    /// Sets the body of the request and returns the instance.
+    fn mock_set_body(self, body: Vec<u8>) -> Self;
+    /// Adds the value to the application state of the request and returns the instance.
+    fn mock_set_state<T: Send + Sync + 'static>(self, state: T) -> Self;
 }
 #[cfg(feature = "test")]
-impl<'a> Mock<'a> for Request<'a> {
+impl Mock for Request {
     fn mock() -> Self {
         Request {
             method: "".to_string(),
             uri: "".to_string(),
+            target: "".to_string(),
+            path: "".to_string(),
+            query: HashMap::new(),
+            query_pairs: Vec::new(),
             protocol: "".to_string(),
+            headers: Vec::new(),
             cookies: HashMap::new(),
             host: "".to_string(),
+            remote_addr: None,
+            secure: false,
             user_agent: "".to_string(),
             input: HashMap::new(),
+            input_pairs: Vec::new(),
             referer: "".to_string(),
             files: HashMap::new(),
-            raw: "".to_string(),
+            body: Bytes::new(),
             middleware: HashMap::new(),
+            extensions: Extensions::new(),
+            state: Arc::default(),
             route: None,
+            rejection: Rejection::default(),
         }
     }
     fn mock_set_method(mut self, method: String) -> Self {
@@ -454,6 +1161,11 @@ impl<'a> Mock<'a> for Request<'a> {
         self
     }
     fn mock_set_uri(mut self, uri: String) -> Self {
+        let (path, query) = Request::split_target(&uri);
+        self.path = path.to_string();
+        self.query_pairs = Request::parse_url_encoded_data(query);
+        self.query = self.query_pairs.iter().cloned().collect();
+        self.target = uri.clone();
         self.uri = uri;
         self
     }
@@ -461,6 +1173,10 @@ impl<'a> Mock<'a> for Request<'a> {
         self.protocol = protocol;
         self
     }
+    fn mock_set_headers(mut self, headers: Vec<(String, String)>) -> Self {
+        self.headers = headers;
+        self
+    }
     fn mock_set_cookies(mut self, cookies: HashMap<String, String>) -> Self {
         self.cookies = cookies;
         self
@@ -469,11 +1185,16 @@ impl<'a> Mock<'a> for Request<'a> {
         self.host = host;
         self
     }
+    fn mock_set_remote_addr(mut self, remote_addr: SocketAddr) -> Self {
+        self.remote_addr = Some(remote_addr);
+        self
+    }
     fn mock_set_user_agent(mut self, user_agent: String) -> Self {
         self.user_agent = user_agent;
         self
     }
     fn mock_set_input(mut self, input: HashMap<String, String>) -> Self {
+        self.input_pairs = input.clone().into_iter().collect();
         self.input = input;
         self
     }
@@ -481,12 +1202,127 @@ impl<'a> Mock<'a> for Request<'a> {
         self.referer = referer;
         self
     }
-    fn mock_set_files(mut self, files: HashMap<String, File<'a>>) -> Self {
+    fn mock_set_files(mut self, files: HashMap<String, File>) -> Self {
         self.files = files;
         self
     }
-    fn mock_set_raw(mut self, raw: String) -> Self {
-        self.raw = raw;
+    fn mock_set_body(mut self, body: Vec<u8>) -> Self {
+        self.body = Bytes::from(body);
         self
     }
-}
\ No newline at end of file
+    fn mock_set_state<T: Send + Sync + 'static>(mut self, state: T) -> Self {
+        Arc::make_mut(&mut self.state).insert(state);
+        self
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_header(name: &str, value: &str) -> Request {
+        Request::new(format!("GET / HTTP/1.1\r\n{}: {}\r\n\r\n", name, value).as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_new_parses_headers() {
+        let request = Request::new(b"GET / HTTP/1.1\r\nHost: localhost:7878\r\nUser-Agent: Mozilla/5.0 (X11; Linux x86_64)\r\nX-Custom: foo,\r\n bar\r\ncookie: a=1; b=2\r\nCookie: c=3\r\n\r\nHost: body").unwrap();
+        assert_eq!(request.get_host(), "localhost:7878");
+        assert_eq!(request.get_user_agent(), "Mozilla/5.0 (X11; Linux x86_64)");
+        assert_eq!(request.get_header("x-custom"), Some("foo, bar"));
+        assert_eq!(request.get_headers("Cookie").len(), 2);
+        assert_eq!(request.get_cookies()["b"], "2");
+        assert_eq!(request.get_cookies()["c"], "3");
+        assert_eq!(request.get_referer(), "");
+        assert_eq!(request.iter_headers().count(), 5);
+    }
+    #[test]
+    fn test_new_separates_query_from_input() {
+        let request = Request::new(b"POST /users/John%20Doe?page=2&q=a%26b+c&debug HTTP/1.1\r\nContent-Type: application/x-www-form-urlencoded\r\n\r\nname=John+Doe&token=a=b").unwrap();
+        assert_eq!(request.get_path(), "/users/John Doe");
+        assert_eq!(request.get_query()["page"], "2");
+        assert_eq!(request.get_query()["q"], "a&b c");
+        assert_eq!(request.get_query()["debug"], "");
+        assert_eq!(request.get_input()["name"], "John Doe");
+        assert_eq!(request.get_input()["token"], "a=b");
+        assert!(!request.get_input().contains_key("page"));
+        let request = Request::new(b"POST / HTTP/1.1\r\nContent-Type: application/json\r\n\r\n{\"a\":\"b=c\"}").unwrap();
+        assert!(request.get_input().is_empty());
+    }
+    #[test]
+    fn test_new_keeps_repeated_fields() {
+        let request = Request::new(b"POST /?page=1&page=2 HTTP/1.1\r\n\r\ntags=a&tags=b").unwrap();
+        assert_eq!(request.get_query_all("page"), vec!["1", "2"]);
+        assert_eq!(request.get_query()["page"], "2");
+        assert_eq!(request.get_all("tags"), vec!["a", "b"]);
+        assert_eq!(request.get_input()["tags"], "b");
+        assert!(request.get_all("missing").is_empty());
+    }
+    #[test]
+    fn test_json_rejections() {
+        #[derive(Deserialize, Debug)]
+        struct User {
+            #[allow(dead_code)]
+            name: String,
+        }
+        let request = Request::new(b"POST / HTTP/1.1\r\nContent-Type: text/plain\r\n\r\n{}").unwrap();
+        assert_eq!(request.json::<User>().unwrap_err().get_status(), 400);
+        let request = Request::new(b"POST / HTTP/1.1\r\nContent-Type: application/json\r\n\r\n{\"name\":").unwrap();
+        assert!(matches!(request.json::<User>(), Err(ExtractError::Syntax(_))));
+        let request = Request::new(b"POST / HTTP/1.1\r\nContent-Type: application/json; charset=utf-8\r\n\r\n{\"age\": 1}").unwrap();
+        let e = request.json::<User>().unwrap_err();
+        assert_eq!(e.get_status(), 422);
+        assert_eq!(request.clone().take_rejection(), None);
+        assert_eq!(request.take_rejection(), Some(e));
+        assert_eq!(request.take_rejection(), None);
+    }
+    #[test]
+    fn test_new_parses_multipart_body() {
+        let mut buffer = b"POST /upload HTTP/1.1\r\nContent-Type: multipart/form-data; boundary=\"--b=1\"\r\n\r\n".to_vec();
+        buffer.extend_from_slice(b"----b=1\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nMy \"photo\"\r\n");
+        buffer.extend_from_slice(b"----b=1\r\nContent-Disposition: form-data; name=\"photo\"; filename=\"caf\xc3\xa9 del mar.png\"\r\nContent-Type: image/png\r\n\r\n");
+        buffer.extend_from_slice(b"\x89PNG\r\n\r\n\xff\xfe\x00\r\n");
+        buffer.extend_from_slice(b"----b=1\r\nContent-Disposition: form-data; name=\"empty\"; filename=\"\"\r\n\r\n\r\n----b=1--\r\n");
+        let request = Request::new(&buffer).unwrap();
+        assert_eq!(request.get_input()["title"], "My \"photo\"");
+        assert_eq!(request.get_files().len(), 1);
+        let photo = &request.get_files()["photo"];
+        assert_eq!(photo.filename, "café del mar.png");
+        assert_eq!(photo.content_type, "image/png");
+        assert_eq!(photo.content().unwrap(), b"\x89PNG\r\n\r\n\xff\xfe\x00");
+        let request = Request::new(b"POST / HTTP/1.1\r\nContent-Type: multipart/form-data; boundary=x\r\n\r\n--x\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\n1").unwrap();
+        assert!(request.get_input().is_empty());
+    }
+    #[test]
+    fn test_request_is_owned() {
+        let mut buffer = b"POST / HTTP/1.1\r\nContent-Type: multipart/form-data; boundary=x\r\n\r\n--x\r\nContent-Disposition: form-data; name=\"f\"; filename=\"a.txt\"\r\n\r\nfoo\r\n--x--".to_vec();
+        let request = Request::new(&buffer).unwrap();
+        buffer.clear();
+        let content = std::thread::spawn(move || request.get_files()["f"].content().unwrap().to_vec())
+            .join()
+            .unwrap();
+        assert_eq!(content, b"foo");
+    }
+    #[test]
+    fn test_accepts() {
+        let request = with_header("Accept", "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8");
+        assert_eq!(request.accepts(&["application/json", "text/html"]), Some("text/html"));
+        assert_eq!(request.accepts(&["application/json", "text/csv"]), Some("application/json"));
+        assert!(!request.wants_json());
+        let request = with_header("Accept", "application/json, text/*;q=0.5, text/csv;q=0");
+        assert_eq!(request.accepts(&["text/html", "application/json"]), Some("application/json"));
+        assert_eq!(request.accepts(&["text/csv", "text/plain"]), Some("text/plain"));
+        assert_eq!(request.accepts(&["text/csv", "image/png"]), None);
+        assert!(request.wants_json());
+        let request = Request::new(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(request.accepts(&["text/html", "application/json"]), Some("text/html"));
+        assert!(!request.wants_json());
+        assert!(!request.is_ajax());
+    }
+    #[test]
+    fn test_accept_languages() {
+        let request = with_header("Accept-Language", "fr-CH, fr;q=0.9, en;q=0.8, de;q=0.7, *;q=0.5");
+        assert_eq!(request.accept_languages(), vec!["fr-CH", "fr", "en", "de"]);
+        let request = with_header("Accept-Language", "");
+        assert!(request.accept_languages().is_empty());
+    }
+}
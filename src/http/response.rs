@@ -2,20 +2,71 @@
 
 #[cfg(feature = "tera")]
 use crate::template;
-use crate::{settings, storage::cookie::Cookie};
+use crate::{handler::asset, http::status::StatusCode, settings, storage::cookie::Cookie};
 use log::{error, warn};
+use std::{
+    convert::TryFrom,
+    fmt, fs,
+    io::{self, Read},
+    path::Path,
+    sync::{Arc, Mutex},
+};
 #[cfg(feature = "tera")]
 use tera::Context;
 
+/// The headers written by the server from the content and the connection of the response.
+const FRAMING_HEADERS: [&str; 4] = [
+    "Content-Length",
+    "Transfer-Encoding",
+    "Connection",
+    "Keep-Alive",
+];
+
 #[derive(Debug, Clone)]
 /// The struct that contains all the data to be sent to the browser.
 pub struct Response {
-    status: String,
+    status: Option<StatusCode>,
     content_type: String,
     location: String,
     cookies: Vec<Cookie>,
-    content: String,
+    headers: Vec<(String, String)>,
+    content: Vec<u8>,
     content_length: String,
+    connection: String,
+    stream: Option<Stream>,
+    transfer_encoding: String,
+    is_head: bool,
+}
+/// The reader of a streamed body, shared by the clones of the `Response` and taken when it's written in the tcp stream.
+#[derive(Clone)]
+struct Stream(Arc<Mutex<Option<Box<dyn Read + Send>>>>);
+impl fmt::Debug for Stream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Stream")
+    }
+}
+/// Adapts an iterator of byte chunks to the `Read` trait.
+struct IterReader<I> {
+    iter: I,
+    chunk: Vec<u8>,
+    position: usize,
+}
+impl<I: Iterator<Item = Vec<u8>>> Read for IterReader<I> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position >= self.chunk.len() {
+            match self.iter.next() {
+                Some(chunk) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.chunk.len() - self.position);
+        buf[..n].copy_from_slice(&self.chunk[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
 }
 impl Response {
     /// Creates and returns an instance of the ´Response´ struct with all the fields empty.
@@ -27,15 +78,24 @@ impl Response {
     /// ```
     pub fn new() -> Response {
         Response {
-            status: "".to_string(),
+            status: None,
             content_type: "".to_string(),
             location: "".to_string(),
             cookies: Vec::new(),
-            content: "".to_string(),
+            headers: Vec::new(),
+            content: Vec::new(),
             content_length: "".to_string(),
+            connection: "".to_string(),
+            stream: None,
+            transfer_encoding: "".to_string(),
+            is_head: false,
         }
     }
     /// Sets the status field of the response and returns the instance.
+    /// Codes from 100 to 599 which are not registered are sent without reason phrase.
+    /// Use `set_status_code` with a `StatusCode` to check the code when it's created.
+    /// # Panics
+    /// Panics in debug builds if the code is not between 100 and 599. Release builds log the error and send 500 instead.
     /// # Examples
     /// ```
     /// use kalgan::http::response::Response;
@@ -45,19 +105,48 @@ impl Response {
     ///
     /// # assert_eq!(response.mock_get_status(), "HTTP/1.1 200 OK".to_string())
     /// ```
-    pub fn set_status(mut self, status_code: i32) -> Self {
-        self.status = match status_code {
-            200 => "HTTP/1.1 200 OK".to_string(),
-            301 => "HTTP/1.1 301 Moved Permanently".to_string(),
-            302 => "HTTP/1.1 302 Found".to_string(),
-            403 => "HTTP/1.1 403 Forbidden".to_string(),
-            404 => "HTTP/1.1 404 Not Found".to_string(),
-            500 => "HTTP/1.1 500 Internal Server Error".to_string(),
-            503 => "HTTP/1.1 503 Service Unavailable".to_string(),
-            _ => "".to_string(),
-        };
+    pub fn set_status(self, status_code: i32) -> Self {
+        let status = u16::try_from(status_code)
+            .ok()
+            .and_then(StatusCode::from_digits);
+        debug_assert!(
+            status.is_some(),
+            "{} is not a valid HTTP status code.",
+            status_code
+        );
+        match status {
+            Some(status) => self.set_status_code(status),
+            None => {
+                error!("{} is not a valid HTTP status code.", status_code);
+                self.set_status(500)
+            }
+        }
+    }
+    /// Sets the status field of the response from a `StatusCode` and returns the instance.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::{response::Response, status::StatusCode};
+    /// # use kalgan::http::response::Mock;
+    ///
+    /// let response: Response = Response::new().set_status_code(StatusCode::new(201).unwrap());
+    ///
+    /// # assert_eq!(response.mock_get_status(), "HTTP/1.1 201 Created".to_string())
+    /// ```
+    pub fn set_status_code(mut self, status: StatusCode) -> Self {
+        self.status = Some(status);
         self
     }
+    /// Returns the status code of the response (if set).
+    /// # Examples
+    /// ```
+    /// use kalgan::http::response::Response;
+    ///
+    /// let response: Response = Response::new().set_status(204);
+    /// # assert_eq!(response.get_status().unwrap().as_u16(), 204);
+    /// ```
+    pub fn get_status(&self) -> Option<StatusCode> {
+        self.status
+    }
     /// Sets the content type field of the response and returns the instance.
     /// # Examples
     /// ```
@@ -73,6 +162,7 @@ impl Response {
         self
     }
     /// Sets the location field of the response and returns the instance.
+    /// Urls with line breaks are ignored.
     /// # Examples
     /// ```
     /// use kalgan::http::response::Response;
@@ -83,9 +173,89 @@ impl Response {
     /// # assert_eq!(response.mock_get_location(), "\r\nLocation: /home".to_string())
     /// ```
     pub fn set_location(mut self, url: &str) -> Self {
-        self.location = format!("\r\nLocation: {}", url);
+        if url.contains(&['\r', '\n'][..]) {
+            warn!(
+                "Location \"{}\" is not valid and has been ignored.",
+                url.escape_debug()
+            );
+        } else {
+            self.location = format!("\r\nLocation: {}", url);
+        }
         self
     }
+    /// Sets the header of the response replacing any previous value and returns the instance.
+    /// Headers with an invalid name or line breaks in the value are ignored,
+    /// and so are the headers which frame the response (`Content-Length`, `Transfer-Encoding`, `Connection` and `Keep-Alive`).
+    /// # Examples
+    /// ```
+    /// use kalgan::http::response::Response;
+    /// # use kalgan::http::response::Mock;
+    ///
+    /// let response: Response = Response::new().set_header("Cache-Control", "no-store");
+    ///
+    /// # assert_eq!(response.mock_get_headers(), vec![("Cache-Control".to_string(), "no-store".to_string())])
+    /// ```
+    pub fn set_header(mut self, name: &str, value: &str) -> Self {
+        match name.to_lowercase().as_str() {
+            "content-type" => self.content_type = String::new(),
+            "location" => self.location = String::new(),
+            _ => (),
+        }
+        self.headers
+            .retain(|(header, _)| !header.eq_ignore_ascii_case(name));
+        self.add_header(name, value)
+    }
+    /// Adds a value to the header of the response keeping the previous ones and returns the instance.
+    /// Headers with an invalid name or line breaks in the value are ignored,
+    /// and so are the headers which frame the response (`Content-Length`, `Transfer-Encoding`, `Connection` and `Keep-Alive`).
+    /// # Examples
+    /// ```
+    /// use kalgan::http::response::Response;
+    /// # use kalgan::http::response::Mock;
+    ///
+    /// let response: Response = Response::new()
+    ///     .add_header("Vary", "Accept")
+    ///     .add_header("Vary", "Accept-Language");
+    ///
+    /// # assert_eq!(response.mock_get_headers().len(), 2)
+    /// ```
+    pub fn add_header(mut self, name: &str, value: &str) -> Self {
+        if name.is_empty()
+            || name.contains(|c: char| c == ':' || c.is_whitespace())
+            || value.contains(&['\r', '\n'][..])
+        {
+            warn!(
+                "Header \"{}\" is not valid and has been ignored.",
+                name.escape_debug()
+            );
+        } else if FRAMING_HEADERS
+            .iter()
+            .any(|header| header.eq_ignore_ascii_case(name))
+        {
+            warn!(
+                "Header \"{}\" is set by the server and has been ignored.",
+                name
+            );
+        } else {
+            self.headers.push((name.to_string(), value.to_string()));
+        }
+        self
+    }
+    /// Returns the values of the given header (case-insensitive) of the response.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::response::Response;
+    ///
+    /// let response: Response = Response::new().add_header("Vary", "Accept");
+    /// # assert_eq!(response.get_header("vary"), vec!["Accept"]);
+    /// ```
+    pub fn get_header(&self, name: &str) -> Vec<&str> {
+        self.headers
+            .iter()
+            .filter(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
     /// Adds a cookie to the response and returns the instance.
     /// # Examples
     /// ```
@@ -138,10 +308,64 @@ impl Response {
     /// # assert_eq!(response.mock_get_content(), "<h1>Hello World</h1>")
     /// ```
     pub fn set_content(mut self, content: &str) -> Self {
-        self.content = format!("{}", content);
+        self.content = content.as_bytes().to_vec();
         self
     }
+    /// Sets the binary content of the response and returns the instance.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::response::Response;
+    /// # use kalgan::http::response::Mock;
+    ///
+    /// let response: Response = Response::new().set_body(vec![0x25, 0x50, 0x44, 0x46]);
+    ///
+    /// # assert_eq!(response.mock_get_body(), vec![0x25, 0x50, 0x44, 0x46])
+    /// ```
+    pub fn set_body(mut self, body: Vec<u8>) -> Self {
+        self.content = body;
+        self
+    }
+    /// Sets the reader the content of the response is streamed from and returns the instance.
+    /// The content is written in the tcp stream as it's read, in chunks unless the content length is set.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::response::Response;
+    ///
+    /// let file = std::fs::File::open("tests/mock/settings.yaml").unwrap();
+    /// let response: Response = Response::new()
+    ///     .set_status(200)
+    ///     .set_content_type("text/yaml")
+    ///     .set_stream(file);
+    /// ```
+    pub fn set_stream<R: Read + Send + 'static>(mut self, reader: R) -> Self {
+        self.content = Vec::new();
+        self.stream = Some(Stream(Arc::new(Mutex::new(Some(Box::new(reader))))));
+        self
+    }
+    /// Sets the iterator the content of the response is streamed from and returns the instance.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::response::Response;
+    ///
+    /// let rows = (1..=1000).map(|id| format!("{},user{}\n", id, id).into_bytes());
+    /// let response: Response = Response::new()
+    ///     .set_status(200)
+    ///     .set_content_type("text/csv")
+    ///     .set_stream_iter(rows);
+    /// ```
+    pub fn set_stream_iter<I>(self, iter: I) -> Self
+    where
+        I: IntoIterator<Item = Vec<u8>>,
+        I::IntoIter: Send + 'static,
+    {
+        self.set_stream(IterReader {
+            iter: iter.into_iter(),
+            chunk: Vec::new(),
+            position: 0,
+        })
+    }
     /// Sets the content length of the response and returns the instance.
+    /// It's only needed for streamed content, otherwise it's computed from the content when the response is created.
     /// # Examples
     /// ```
     /// use kalgan::http::response::Response;
@@ -155,7 +379,53 @@ impl Response {
         self.content_length = format!("\r\nContent-Length: {}", content_length);
         self
     }
+    /// Sets the connection field of the response and returns the instance.
+    /// Streamed content of unknown length is sent in chunks if the connection is kept alive,
+    /// since it's the only way for the browser to know where the response ends.
+    pub(crate) fn set_connection(mut self, keep_alive: bool, timeout: u64) -> Self {
+        if self.stream.is_some() && self.content_length.is_empty() && keep_alive {
+            self.transfer_encoding = "\r\nTransfer-Encoding: chunked".to_string();
+        }
+        if keep_alive {
+            self.connection = format!(
+                "\r\nConnection: keep-alive\r\nKeep-Alive: timeout={}",
+                timeout
+            );
+        } else {
+            self.connection = "\r\nConnection: close".to_string();
+        }
+        self
+    }
+    /// Marks the response as the answer to a HEAD request, so the headers are sent without the content, and returns the instance.
+    pub(crate) fn set_head(mut self, is_head: bool) -> Self {
+        self.is_head = is_head;
+        self
+    }
+    /// Checks whether the connection is kept alive after the response is sent.
+    pub(crate) fn is_keep_alive(&self) -> bool {
+        self.connection.starts_with("\r\nConnection: keep-alive")
+    }
+    /// Checks whether the content of the response is streamed.
+    pub(crate) fn is_stream(&self) -> bool {
+        self.stream.is_some()
+    }
+    /// Checks whether the streamed content of the response is sent in chunks.
+    pub(crate) fn is_chunked(&self) -> bool {
+        !self.transfer_encoding.is_empty()
+    }
+    /// Takes the reader of the streamed content to be written in the tcp stream.
+    /// `None` is returned for the answer to a HEAD request.
+    pub(crate) fn take_stream(&self) -> Option<Box<dyn Read + Send>> {
+        if self.is_head {
+            return None;
+        }
+        self.stream
+            .as_ref()
+            .and_then(|stream| stream.0.lock().unwrap().take())
+    }
     /// Creates a string with all the response data to be sent to the browser.
+    /// The content length is computed from the content, unless it's streamed.
+    /// The streamed content (if any) is not included and must be written after it.
     /// # Examples
     /// ```
     /// use kalgan::http::response::Response;
@@ -163,16 +433,55 @@ impl Response {
     /// let response: Vec<u8> = Response::new().create();
     /// ```
     pub fn create(&self) -> Vec<u8> {
-        format!(
-            "{}{}{}{}\r\n\r\n{}",
-            self.status,
+        let mut bytes = format!(
+            "{}{}{}{}{}{}{}{}\r\n\r\n",
+            self.create_status(),
             self.location,
             self.create_cookies(),
             self.content_type,
-            self.content
+            self.create_content_length(),
+            self.transfer_encoding,
+            self.create_headers(),
+            self.connection,
         )
-        .as_bytes()
-        .to_vec()
+        .into_bytes();
+        if !self.is_head && self.has_content() {
+            bytes.extend_from_slice(&self.content);
+        }
+        bytes
+    }
+    /// Creates the status line to be sent to the browser.
+    fn create_status(&self) -> String {
+        match &self.status {
+            Some(status) => format!("HTTP/1.1 {}", status),
+            None => "".to_string(),
+        }
+    }
+    /// Creates the content length header to be sent to the browser.
+    fn create_content_length(&self) -> String {
+        if self.stream.is_some() {
+            self.content_length.clone()
+        } else if self.has_content() {
+            format!("\r\nContent-Length: {}", self.content.len())
+        } else {
+            "".to_string()
+        }
+    }
+    /// Checks whether the status code of the response allows content (all but 1xx, 204 and 304).
+    fn has_content(&self) -> bool {
+        match self.status {
+            Some(status) => {
+                !status.is_informational() && status.as_u16() != 204 && status.as_u16() != 304
+            }
+            None => true,
+        }
+    }
+    /// Creates a string with all the headers to be sent to the browser.
+    fn create_headers(&self) -> String {
+        self.headers
+            .iter()
+            .map(|(name, value)| format!("\r\n{}: {}", name, value))
+            .collect()
     }
     /// Creates a string with all the cookies data to be sent to the browser.
     fn create_cookies(&self) -> String {
@@ -197,7 +506,6 @@ pub fn json(contents: &str) -> Response {
     Response::new()
         .set_status(200)
         .set_content_type("application/json; charset=UTF-8")
-        .set_content_length(contents.len())
         .set_content(&contents)
 }
 /// Returns a xml `Response` object for the given content.
@@ -222,7 +530,6 @@ pub fn xml(contents: &str) -> Response {
     Response::new()
         .set_status(200)
         .set_content_type("application/xml; charset=UTF-8")
-        .set_content_length(contents.len())
         .set_content(&contents)
 }
 /// Returns a redirect `Response` object for the given url.
@@ -238,6 +545,83 @@ pub fn xml(contents: &str) -> Response {
 pub fn redirect(url: String) -> Response {
     Response::new().set_status(302).set_location(&url)
 }
+/// Returns a `Response` object streaming the content of the given file to be displayed by the browser.
+/// # Errors
+/// Returns the error message if the file cannot be opened.
+/// # Examples
+/// ```
+/// use kalgan::http::response;
+/// use kalgan::http::response::Response;
+/// # use kalgan::http::response::Mock;
+///
+/// let response: Response = response::file("tests/mock/settings.yaml").unwrap();
+/// # let length = std::fs::metadata("tests/mock/settings.yaml").unwrap().len();
+/// # assert!(String::from_utf8(response.create()).unwrap().contains(&format!("\r\nContent-Length: {}\r\n", length)));
+/// # assert_eq!(response.clone().mock_get_content_type(), "\r\nContent-Type: application/octet-stream;");
+/// # assert_eq!(response.mock_get_headers(), vec![("Content-Disposition".to_string(), "inline; filename=\"settings.yaml\"".to_string())])
+/// ```
+pub fn file(path: impl AsRef<Path>) -> Result<Response, String> {
+    let path = path.as_ref();
+    let cannot_read = |e: io::Error| format!("File \"{}\" cannot be read: {}", path.display(), e);
+    let file = fs::File::open(path).map_err(cannot_read)?;
+    let metadata = file.metadata().map_err(cannot_read)?;
+    if !metadata.is_file() {
+        return Err(format!("\"{}\" is not a file.", path.display()));
+    }
+    let filename = match path.file_name() {
+        Some(filename) => filename.to_string_lossy().to_string(),
+        None => path.display().to_string(),
+    };
+    Ok(create_file_response(&filename, "inline")
+        .set_content_length(metadata.len() as usize)
+        .set_stream(file))
+}
+/// Returns a `Response` object with the given content to be downloaded by the browser as the given filename.
+/// # Examples
+/// ```
+/// use kalgan::http::response;
+/// use kalgan::http::response::Response;
+/// # use kalgan::http::response::Mock;
+///
+/// let response: Response = response::download(b"name,surname\nJohn,Doe".to_vec(), "users.csv");
+/// # assert_eq!(response.clone().mock_get_content_type(), "\r\nContent-Type: text/csv;");
+/// # assert!(String::from_utf8(response.create()).unwrap().contains("\r\nContent-Length: 21\r\n"));
+/// # assert_eq!(response.mock_get_headers(), vec![("Content-Disposition".to_string(), "attachment; filename=\"users.csv\"".to_string())])
+/// ```
+pub fn download(contents: Vec<u8>, filename: &str) -> Response {
+    create_file_response(filename, "attachment").set_body(contents)
+}
+/// Returns a `Response` object without content for the given filename and disposition type.
+fn create_file_response(filename: &str, disposition: &str) -> Response {
+    Response::new()
+        .set_status(200)
+        .set_content_type(asset::get_content_type(filename))
+        .set_header(
+            "Content-Disposition",
+            &get_content_disposition(disposition, filename),
+        )
+}
+/// Returns the value of the `Content-Disposition` header for the given disposition type and filename.
+fn get_content_disposition(disposition: &str, filename: &str) -> String {
+    let fallback: String = filename
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' => '_',
+            c if c.is_ascii() && !c.is_ascii_control() => c,
+            _ => '_',
+        })
+        .collect();
+    if fallback == filename {
+        format!("{}; filename=\"{}\"", disposition, filename)
+    } else {
+        format!(
+            "{}; filename=\"{}\"; filename*=UTF-8''{}",
+            disposition,
+            fallback,
+            urlencoding::encode(filename)
+        )
+    }
+}
 #[cfg(feature = "tera")]
 /// Returns a html `Response` object for the given template.
 /// # Examples
@@ -277,7 +661,6 @@ fn create_html_response(status: i32, filename: &str, parameters: Context) -> Res
     Response::new()
         .set_status(status)
         .set_content_type("text/html; charset=UTF-8")
-        .set_content_length(contents.len())
         .set_content(&contents)
 }
 #[cfg(feature = "test")]
@@ -293,13 +676,17 @@ pub trait Mock {
     fn mock_get_cookies(self) -> Vec<Cookie>;
     /// Returns the content of the `Response` object to be used in testing.
     fn mock_get_content(self) -> String;
+    /// Returns the binary content of the `Response` object to be used in testing.
+    fn mock_get_body(self) -> Vec<u8>;
     /// Returns the content length of the `Response` object to be used in testing.
     fn mock_get_content_length(self) -> String;
+    /// Returns the headers of the `Response` object to be used in testing.
+    fn mock_get_headers(self) -> Vec<(String, String)>;
 }
 #[cfg(feature = "test")]
 impl Mock for Response {
     fn mock_get_status(self) -> String {
-        self.status
+        self.create_status()
     }
     fn mock_get_content_type(self) -> String {
         self.content_type
@@ -311,9 +698,133 @@ impl Mock for Response {
         self.cookies
     }
     fn mock_get_content(self) -> String {
+        String::from_utf8_lossy(&self.content).to_string()
+    }
+    fn mock_get_body(self) -> Vec<u8> {
         self.content
     }
     fn mock_get_content_length(self) -> String {
         self.content_length
     }
+    fn mock_get_headers(self) -> Vec<(String, String)> {
+        self.headers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_binary() {
+        let body = vec![0xff, 0x00, 0xfe];
+        let bytes = Response::new()
+            .set_status(200)
+            .set_body(body.clone())
+            .create();
+        assert!(bytes.starts_with(b"HTTP/1.1 200 OK"));
+        assert!(bytes.ends_with(&body));
+    }
+    #[test]
+    fn test_set_stream_iter() {
+        let response = Response::new()
+            .set_stream_iter(vec![b"foo".to_vec(), Vec::new(), b"=bar".to_vec()])
+            .set_connection(true, 5);
+        let mut content = String::new();
+        response
+            .take_stream()
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "foo=bar");
+        assert!(response.is_chunked());
+        assert!(response.take_stream().is_none());
+    }
+    #[test]
+    fn test_create_content_length() {
+        let bytes = Response::new()
+            .set_status(302)
+            .set_location("/home")
+            .create();
+        assert!(String::from_utf8(bytes)
+            .unwrap()
+            .ends_with("Content-Length: 0\r\n\r\n"));
+        let bytes = Response::new()
+            .set_status(200)
+            .set_content("Hello World")
+            .set_head(true)
+            .create();
+        assert!(String::from_utf8(bytes)
+            .unwrap()
+            .ends_with("Content-Length: 11\r\n\r\n"));
+        let bytes = Response::new()
+            .set_status(204)
+            .set_content("Hello World")
+            .create();
+        assert_eq!(bytes, b"HTTP/1.1 204 No Content\r\n\r\n");
+    }
+    #[test]
+    fn test_set_location_ignores_line_breaks() {
+        let response = Response::new().set_location("/\r\nSet-Cookie: x=1");
+        assert_eq!(response.location, "");
+    }
+    #[test]
+    fn test_set_header_ignores_framing_headers() {
+        let bytes = Response::new()
+            .set_status(200)
+            .set_header("Content-Length", "1")
+            .set_header("transfer-encoding", "chunked")
+            .add_header("Connection", "upgrade")
+            .set_content("Hello World")
+            .set_connection(false, 5)
+            .create();
+        let response = String::from_utf8(bytes).unwrap();
+        assert_eq!(response.matches("Content-Length").count(), 1);
+        assert!(response.contains("Content-Length: 11\r\n"));
+        assert!(!response.contains("Transfer-Encoding"));
+        assert_eq!(response.matches("Connection").count(), 1);
+    }
+    #[test]
+    fn test_set_status_with_unregistered_code() {
+        let response = Response::new().set_status(599);
+        assert_eq!(response.get_status().unwrap().as_u16(), 599);
+        assert!(response.create().starts_with(b"HTTP/1.1 599 \r\n"));
+    }
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "600 is not a valid HTTP status code.")]
+    fn test_set_status_with_invalid_code() {
+        let _ = Response::new().set_status(600);
+    }
+    #[test]
+    fn test_file() {
+        let folder = tempfile::tempdir().unwrap();
+        let path = folder.path().join("report.txt");
+        fs::write(&path, "Hello World").unwrap();
+        let response = file(&path).unwrap();
+        assert!(response.is_stream());
+        assert!(String::from_utf8(response.create())
+            .unwrap()
+            .contains("\r\nContent-Length: 11\r\n"));
+        let mut content = String::new();
+        response
+            .take_stream()
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "Hello World");
+        assert!(file(folder.path()).is_err());
+        assert!(file(folder.path().join("missing.txt")).is_err());
+    }
+    #[test]
+    fn test_get_content_disposition() {
+        assert_eq!(
+            get_content_disposition("attachment", "report.pdf"),
+            "attachment; filename=\"report.pdf\""
+        );
+        assert_eq!(
+            get_content_disposition("attachment", "informe \"año\".pdf"),
+            "attachment; filename=\"informe _a_o_.pdf\"; filename*=UTF-8''informe%20%22a%C3%B1o%22.pdf"
+        );
+    }
 }
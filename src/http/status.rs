@@ -0,0 +1,195 @@
+//! Module for the status code of the response object.
+
+use std::{convert::TryFrom, fmt};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The HTTP status code of a `Response`.
+pub struct StatusCode(u16);
+impl StatusCode {
+    /// Creates and returns an instance of the `StatusCode` struct for the given code.
+    /// # Errors
+    /// Returns the error message if the code is not a registered HTTP status code.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::status::StatusCode;
+    ///
+    /// let status: StatusCode = StatusCode::new(404).unwrap();
+    /// # assert_eq!(status.reason(), "Not Found");
+    /// # assert!(StatusCode::new(42).is_err());
+    /// ```
+    pub fn new(code: u16) -> Result<StatusCode, String> {
+        if get_reason(code).is_some() {
+            Ok(StatusCode(code))
+        } else {
+            Err(format!("{} is not a valid HTTP status code.", code))
+        }
+    }
+    /// Creates and returns an instance of the `StatusCode` struct for a code from 100 to 599 which may not be registered.
+    pub(crate) fn from_digits(code: u16) -> Option<StatusCode> {
+        if (100..600).contains(&code) {
+            Some(StatusCode(code))
+        } else {
+            None
+        }
+    }
+    /// Returns the numeric value of the status code.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::status::StatusCode;
+    ///
+    /// let code: u16 = StatusCode::new(201).unwrap().as_u16();
+    /// # assert_eq!(code, 201);
+    /// ```
+    pub fn as_u16(&self) -> u16 {
+        self.0
+    }
+    /// Returns the reason phrase of the status code, which is empty for the codes that are not registered.
+    /// # Examples
+    /// ```
+    /// use kalgan::http::status::StatusCode;
+    ///
+    /// let reason: &str = StatusCode::new(422).unwrap().reason();
+    /// # assert_eq!(reason, "Unprocessable Content");
+    /// ```
+    pub fn reason(&self) -> &'static str {
+        get_reason(self.0).unwrap_or("")
+    }
+    /// Returns `true` for the 1xx status codes.
+    pub fn is_informational(&self) -> bool {
+        (100..200).contains(&self.0)
+    }
+    /// Returns `true` for the 2xx status codes.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.0)
+    }
+    /// Returns `true` for the 3xx status codes.
+    pub fn is_redirection(&self) -> bool {
+        (300..400).contains(&self.0)
+    }
+    /// Returns `true` for the 4xx status codes.
+    pub fn is_client_error(&self) -> bool {
+        (400..500).contains(&self.0)
+    }
+    /// Returns `true` for the 5xx status codes.
+    pub fn is_server_error(&self) -> bool {
+        (500..600).contains(&self.0)
+    }
+}
+impl TryFrom<i32> for StatusCode {
+    type Error = String;
+    fn try_from(code: i32) -> Result<Self, Self::Error> {
+        match u16::try_from(code) {
+            Ok(code) => StatusCode::new(code),
+            Err(_) => Err(format!("{} is not a valid HTTP status code.", code)),
+        }
+    }
+}
+impl fmt::Display for StatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.0, self.reason())
+    }
+}
+/// Returns the reason phrase of the given code if it's a registered HTTP status code.
+fn get_reason(code: u16) -> Option<&'static str> {
+    let reason = match code {
+        100 => "Continue",
+        101 => "Switching Protocols",
+        102 => "Processing",
+        103 => "Early Hints",
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        203 => "Non-Authoritative Information",
+        204 => "No Content",
+        205 => "Reset Content",
+        206 => "Partial Content",
+        207 => "Multi-Status",
+        208 => "Already Reported",
+        226 => "IM Used",
+        300 => "Multiple Choices",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        304 => "Not Modified",
+        305 => "Use Proxy",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        402 => "Payment Required",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        406 => "Not Acceptable",
+        407 => "Proxy Authentication Required",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        410 => "Gone",
+        411 => "Length Required",
+        412 => "Precondition Failed",
+        413 => "Payload Too Large",
+        414 => "URI Too Long",
+        415 => "Unsupported Media Type",
+        416 => "Range Not Satisfiable",
+        417 => "Expectation Failed",
+        418 => "I'm a teapot",
+        421 => "Misdirected Request",
+        422 => "Unprocessable Content",
+        423 => "Locked",
+        424 => "Failed Dependency",
+        425 => "Too Early",
+        426 => "Upgrade Required",
+        428 => "Precondition Required",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        451 => "Unavailable For Legal Reasons",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        505 => "HTTP Version Not Supported",
+        506 => "Variant Also Negotiates",
+        507 => "Insufficient Storage",
+        508 => "Loop Detected",
+        510 => "Not Extended",
+        511 => "Network Authentication Required",
+        _ => return None,
+    };
+    Some(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_from() {
+        assert_eq!(StatusCode::try_from(204).unwrap().as_u16(), 204);
+        assert!(StatusCode::try_from(-1).is_err());
+        assert!(StatusCode::try_from(99999).is_err());
+        assert!(StatusCode::try_from(599).is_err());
+    }
+    #[test]
+    fn test_from_digits() {
+        assert_eq!(StatusCode::from_digits(599).unwrap().to_string(), "599 ");
+        assert!(StatusCode::from_digits(42).is_none());
+        assert!(StatusCode::from_digits(600).is_none());
+        assert!(StatusCode::from_digits(999).is_none());
+    }
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            StatusCode::new(429).unwrap().to_string(),
+            "429 Too Many Requests"
+        );
+    }
+    #[test]
+    fn test_classes() {
+        assert!(StatusCode::new(101).unwrap().is_informational());
+        assert!(StatusCode::new(201).unwrap().is_success());
+        assert!(StatusCode::new(304).unwrap().is_redirection());
+        assert!(StatusCode::new(409).unwrap().is_client_error());
+        assert!(StatusCode::new(502).unwrap().is_server_error());
+    }
+}
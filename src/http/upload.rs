@@ -0,0 +1,247 @@
+//! Module for the uploads which read the `multipart/form-data` body of the request as it's received,
+//! writing the files bigger than `server.upload.spool_threshold` to disk.
+
+use crate::{
+    http::{multipart, request::File},
+    settings,
+};
+use std::{collections::HashMap, fmt, io::Write, path::PathBuf};
+use tempfile::NamedTempFile;
+
+/// The reasons why an upload cannot be read.
+#[derive(Debug)]
+pub(crate) enum UploadError {
+    /// The body or one of its fields exceeds the upload size limits.
+    TooLarge,
+    /// The body is not a valid `multipart/form-data` one.
+    Malformed(String),
+    /// The file cannot be written to disk.
+    Io(std::io::Error),
+}
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UploadError::TooLarge => write!(f, "The upload exceeds the maximum size allowed."),
+            UploadError::Malformed(message) => write!(f, "{}", message),
+            UploadError::Io(e) => write!(f, "The upload cannot be written to disk: {}", e),
+        }
+    }
+}
+impl From<String> for UploadError {
+    fn from(message: String) -> Self {
+        UploadError::Malformed(message)
+    }
+}
+impl From<std::io::Error> for UploadError {
+    fn from(e: std::io::Error) -> Self {
+        UploadError::Io(e)
+    }
+}
+/// The input data and files of a `multipart/form-data` body.
+#[derive(Debug, Default)]
+pub(crate) struct Upload {
+    pub(crate) input: Vec<(String, String)>,
+    pub(crate) files: HashMap<String, File>,
+}
+/// The size limits and the temporary directory of the uploads.
+#[derive(Debug, Clone)]
+pub(crate) struct Limits {
+    pub(crate) max_size: usize,
+    pub(crate) max_field_size: usize,
+    pub(crate) spool_threshold: usize,
+    pub(crate) temp_dir: PathBuf,
+}
+impl Limits {
+    /// Returns the limits of the `server.upload` settings.
+    pub(crate) fn from_settings() -> Limits {
+        Limits {
+            max_size: settings::upload_max_size(),
+            max_field_size: settings::upload_max_field_size(),
+            spool_threshold: settings::upload_spool_threshold(),
+            temp_dir: settings::upload_temp_dir(),
+        }
+    }
+    /// Returns the limits read when the settings were loaded.
+    pub(crate) fn get() -> Limits {
+        crate::UPLOAD_LIMITS
+            .lock()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(Limits::from_settings)
+    }
+}
+/// Where the content of the field being received is kept.
+enum Spool {
+    Memory(Vec<u8>),
+    Disk(NamedTempFile),
+}
+/// The field being received.
+struct Field {
+    part: multipart::Part,
+    spool: Spool,
+    size: usize,
+}
+/// Reads a `multipart/form-data` body fed in chunks of any size.
+pub(crate) struct Uploader {
+    parser: multipart::Parser,
+    limits: Limits,
+    field: Option<Field>,
+    size: usize,
+    upload: Upload,
+}
+impl Uploader {
+    /// Creates and returns an instance of `Uploader` for the given boundary and limits.
+    pub(crate) fn new(boundary: &str, limits: Limits) -> Uploader {
+        Uploader {
+            parser: multipart::Parser::new(boundary),
+            limits,
+            field: None,
+            size: 0,
+            upload: Upload::default(),
+        }
+    }
+    /// Reads the given chunk of the body.
+    /// # Errors
+    /// Returns `UploadError::TooLarge` as soon as a limit is exceeded.
+    pub(crate) fn feed(&mut self, chunk: &[u8]) -> Result<(), UploadError> {
+        self.size += chunk.len();
+        if self.size > self.limits.max_size {
+            return Err(UploadError::TooLarge);
+        }
+        let Uploader {
+            parser,
+            limits,
+            field,
+            upload,
+            ..
+        } = self;
+        parser.feed(chunk, &mut |event| match event {
+            multipart::Event::Part(part) => {
+                *field = Some(Field {
+                    part,
+                    spool: Spool::Memory(Vec::new()),
+                    size: 0,
+                });
+                Ok(())
+            }
+            multipart::Event::Data(data, _) => match field.as_mut() {
+                Some(field) => write(field, data, limits),
+                None => Ok(()),
+            },
+            multipart::Event::End => {
+                if let Some(field) = field.take() {
+                    store(field, upload)?;
+                }
+                Ok(())
+            }
+        })
+    }
+    /// Returns the input data and files once the whole body has been read.
+    /// # Errors
+    /// Returns `UploadError::Malformed` if the body is incomplete.
+    pub(crate) fn finish(self) -> Result<Upload, UploadError> {
+        self.parser.finish()?;
+        Ok(self.upload)
+    }
+}
+/// Appends the data to the content of the field, moving the content of files to disk when it reaches the spool threshold.
+fn write(field: &mut Field, data: &[u8], limits: &Limits) -> Result<(), UploadError> {
+    field.size += data.len();
+    if field.size > limits.max_field_size {
+        return Err(UploadError::TooLarge);
+    }
+    if let Spool::Memory(content) = &field.spool {
+        if field.part.filename.is_some() && field.size > limits.spool_threshold {
+            let mut file = NamedTempFile::new_in(&limits.temp_dir)?;
+            file.write_all(content)?;
+            field.spool = Spool::Disk(file);
+        }
+    }
+    match &mut field.spool {
+        Spool::Memory(content) => content.extend_from_slice(data),
+        Spool::Disk(file) => file.write_all(data)?,
+    }
+    Ok(())
+}
+/// Adds the received field to the input data or the files of the upload.
+fn store(field: Field, upload: &mut Upload) -> Result<(), UploadError> {
+    let Field { part, spool, size } = field;
+    match part.filename {
+        // Empty file inputs are sent without file name.
+        Some(filename) if filename.is_empty() => (),
+        Some(filename) => {
+            let file = match spool {
+                Spool::Memory(content) => File::new(filename, part.content_type, content),
+                Spool::Disk(mut file) => {
+                    file.flush()?;
+                    File::from_temp(filename, part.content_type, file.into_temp_path(), size)
+                }
+            };
+            upload.files.insert(part.name, file);
+        }
+        None => {
+            if let Spool::Memory(content) = spool {
+                upload
+                    .input
+                    .push((part.name, String::from_utf8_lossy(&content).to_string()));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &[u8] = b"--b\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nHoliday\r\n--b\r\nContent-Disposition: form-data; name=\"small\"; filename=\"a.txt\"\r\n\r\nfoo\r\n--b\r\nContent-Disposition: form-data; name=\"big\"; filename=\"b.bin\"\r\nContent-Type: application/octet-stream\r\n\r\n0123456789\x00\xff0123456789\r\n--b--\r\n";
+
+    fn get_limits(max_size: usize, max_field_size: usize) -> Limits {
+        Limits {
+            max_size,
+            max_field_size,
+            spool_threshold: 16,
+            temp_dir: std::env::temp_dir(),
+        }
+    }
+
+    #[test]
+    fn test_get_limits() {
+        crate::tests::set_config();
+        let limits = Limits::get();
+        assert_eq!(limits.max_size, 4096);
+        assert_eq!(limits.max_field_size, 64);
+        assert_eq!(limits.spool_threshold, 16);
+    }
+    #[test]
+    fn test_feed_spools_big_files() {
+        let mut uploader = Uploader::new("b", get_limits(1024, 64));
+        for chunk in BODY.chunks(5) {
+            uploader.feed(chunk).unwrap();
+        }
+        let upload = uploader.finish().unwrap();
+        assert_eq!(
+            upload.input,
+            vec![("title".to_string(), "Holiday".to_string())]
+        );
+        let small = &upload.files["small"];
+        assert_eq!(small.content(), Some(&b"foo"[..]));
+        assert!(small.path().is_none());
+        let big = &upload.files["big"];
+        assert_eq!(big.size(), 22);
+        assert_eq!(
+            std::fs::read(big.path().unwrap()).unwrap(),
+            b"0123456789\x00\xff0123456789"
+        );
+        let path = big.path().unwrap().to_path_buf();
+        drop(upload);
+        assert!(!path.exists());
+    }
+    #[test]
+    fn test_feed_with_exceeded_limits() {
+        let mut uploader = Uploader::new("b", get_limits(1024, 16));
+        assert!(matches!(uploader.feed(BODY), Err(UploadError::TooLarge)));
+        let mut uploader = Uploader::new("b", get_limits(64, 64));
+        assert!(matches!(uploader.feed(BODY), Err(UploadError::TooLarge)));
+    }
+}
@@ -7,15 +7,26 @@
 extern crate lazy_static;
 
 pub mod handler {
-    mod asset;
+    pub mod app;
+    pub(crate) mod asset;
     pub(crate) mod controller;
     mod error;
+    #[cfg(feature = "async")]
+    pub mod future;
     pub mod middleware;
     pub(crate) mod resolver;
+    pub(crate) mod routes;
 }
 pub mod http {
+    pub mod extensions;
+    pub mod extract;
+    pub(crate) mod multipart;
+    mod params;
+    pub(crate) mod proxy;
     pub mod request;
     pub mod response;
+    pub mod status;
+    pub(crate) mod upload;
 }
 pub mod service {
     #[cfg(feature = "session")]
@@ -33,8 +44,13 @@ pub mod service {
     pub mod url;
 }
 mod server {
+    #[cfg(feature = "async")]
+    pub(crate) mod async_listener;
+    pub(crate) mod connection;
     pub(crate) mod tcp_listener;
     pub(crate) mod thread_pool;
+    #[cfg(feature = "tls")]
+    pub(crate) mod tls;
     pub(crate) mod worker;
 }
 pub mod storage {
@@ -45,16 +61,35 @@ pub mod storage {
 pub mod settings;
 #[cfg(feature = "tera")]
 pub mod template;
+#[cfg(feature = "async")]
 use crate::{
-    handler::middleware::Outcome,
+    handler::{
+        future::{AsyncController, AsyncMiddleware},
+        middleware::AsyncMiddlewareFactory,
+    },
+    server::async_listener,
+};
+use crate::{
+    handler::middleware::{MiddlewareFactory, MiddlewareFn},
     http::{request::Request, response::Response},
     server::tcp_listener,
 };
 #[cfg(feature = "cache")]
 pub use kalgan_cache;
+pub use crate::handler::app::App;
+pub use crate::server::tcp_listener::ServerHandle;
 use chrono::{offset::Utc, NaiveTime};
-use log::trace;
-use std::{collections::HashMap, sync::Mutex};
+use log::{error, trace};
+use signal_hook::{
+    consts::{SIGINT, SIGTERM},
+    flag,
+    iterator::Signals,
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    thread,
+};
 
 /// Stores the current time used to refresh the config parameters and version static files in templates.
 struct Refresh {
@@ -68,6 +103,8 @@ struct TeraConfig {
 lazy_static! {
     pub(crate) static ref CONFIG: Mutex<kalgan_config::Config> = Mutex::new(kalgan_config::Config{ collection: HashMap::new() });
     pub(crate) static ref ROUTES: Mutex<kalgan_router::Router> = Mutex::new(kalgan_router::Router{ collection: Vec::new() });
+    pub(crate) static ref ROUTE_PATTERNS: Mutex<Vec<handler::routes::Pattern>> = Mutex::new(Vec::new());
+    pub(crate) static ref UPLOAD_LIMITS: Mutex<Option<http::upload::Limits>> = Mutex::new(None);
     pub(crate) static ref REFRESH: Mutex<Refresh> = Mutex::new(Refresh { time: Utc::now().time() });
 }
 #[cfg(feature = "tera")]
@@ -77,23 +114,130 @@ lazy_static! {
     };
     pub(crate) static ref TERA_CONFIG: Mutex<TeraConfig> = Mutex::new(TeraConfig { config: None });
 }
+#[cfg(feature = "tokio")]
+lazy_static! {
+    pub(crate) static ref RUNTIME: tokio::runtime::Runtime = tokio::runtime::Runtime::new().unwrap();
+}
 #[cfg(feature = "kalgan_i18n")]
 lazy_static! {
     pub(crate) static ref MESSAGES: Mutex<kalgan_i18n::Messages> = Mutex::new(kalgan_i18n::Messages{ collection: HashMap::new() });
 }
 /// Parses configuration parameters, routes and translation messages and finally starts the http server.
+/// The server is shut down gracefully when a SIGTERM or SIGINT signal is received; a second signal exits immediately.
 pub fn run(
     settings_file_path: &'static str,
     controller: fn(&Request, &str) -> Result<Response, String>,
-    middleware: Option<fn(&mut Request, &str) -> Result<Outcome, String>>,
+    middleware: Option<MiddlewareFn>,
+) {
+    wait_for_signals(start(settings_file_path, controller, middleware));
+}
+/// Parses configuration parameters, routes and translation messages and starts the http server in a new thread.
+/// Returns the `ServerHandle` used to shut the server down.
+/// # Errors
+/// Returns the error message if the server cannot be started.
+pub fn start(
+    settings_file_path: &'static str,
+    controller: fn(&Request, &str) -> Result<Response, String>,
+    middleware: Option<MiddlewareFn>,
+) -> Result<ServerHandle, String> {
+    boot(settings_file_path);
+    tcp_listener::start(
+        settings_file_path,
+        Arc::new(controller),
+        middleware.map(|middleware| Arc::new(middleware) as Arc<dyn MiddlewareFactory>),
+        Arc::default(),
+    )
+}
+#[cfg(feature = "async")]
+/// Parses configuration parameters, routes and translation messages and finally starts the async http server on tokio.
+/// The server is shut down gracefully when a SIGTERM or SIGINT signal is received; a second signal exits immediately.
+/// # Examples
+/// ```no_run
+/// use kalgan::handler::future::BoxFuture;
+/// use kalgan::http::{request::Request, response::Response};
+///
+/// fn resolver<'a>(_request: &'a Request, controller: &'a str) -> BoxFuture<'a, Result<Response, String>> {
+///     Box::pin(async move { Err(format!("Controller {} not found in resolver.", &controller)) })
+/// }
+/// kalgan::run_async("settings.yaml", resolver, None);
+/// ```
+pub fn run_async(
+    settings_file_path: &'static str,
+    controller: AsyncController,
+    middleware: Option<AsyncMiddleware>,
 ) {
+    wait_for_signals(start_async(settings_file_path, controller, middleware));
+}
+#[cfg(feature = "async")]
+/// Parses configuration parameters, routes and translation messages and starts the async http server in a new thread.
+/// Returns the `ServerHandle` used to shut the server down.
+/// # Errors
+/// Returns the error message if the server cannot be started.
+pub fn start_async(
+    settings_file_path: &'static str,
+    controller: AsyncController,
+    middleware: Option<AsyncMiddleware>,
+) -> Result<ServerHandle, String> {
+    boot(settings_file_path);
+    async_listener::start(
+        settings_file_path,
+        controller,
+        middleware.map(|middleware| Arc::new(middleware) as Arc<dyn AsyncMiddlewareFactory>),
+        Arc::default(),
+    )
+}
+#[cfg(feature = "tokio")]
+/// Returns the tokio runtime shared by the async http server and the handlers.
+///
+/// Async controllers already run inside it, so futures like the `service::db` pools can be awaited directly.
+/// Sync controllers can use it to block on them:
+/// ```no_run
+/// let result = kalgan::runtime().block_on(async { 1984 });
+/// ```
+pub fn runtime() -> &'static tokio::runtime::Runtime {
+    &RUNTIME
+}
+/// Parses configuration parameters, routes and translation messages.
+fn boot(settings_file_path: &str) {
     set_config(settings_file_path);
     set_routes();
     #[cfg(feature = "kalgan_i18n")]
     {
         set_messages();
     }
-    tcp_listener::start(settings_file_path, controller, middleware);
+}
+/// Registers the SIGTERM and SIGINT handlers and waits until the server is stopped.
+fn wait_for_signals(server: Result<ServerHandle, String>) {
+    match server {
+        Ok(server) => {
+            let flag = server.get_shutdown_flag();
+            let address = server.local_addr();
+            for signal in &[SIGTERM, SIGINT] {
+                if let Err(e) = flag::register_conditional_shutdown(*signal, 1, Arc::clone(&flag)) {
+                    error!("Signal {} cannot be handled.", signal);
+                    error!("{}", e);
+                }
+            }
+            match Signals::new(&[SIGTERM, SIGINT]) {
+                Ok(mut signals) => {
+                    thread::spawn(move || {
+                        for _ in signals.forever() {
+                            tcp_listener::stop(address, &flag);
+                        }
+                    });
+                }
+                Err(e) => {
+                    error!("Signals cannot be handled.");
+                    error!("{}", e);
+                }
+            }
+            server.wait();
+        }
+        Err(e) => {
+            error!("App cannot be started.");
+            error!("{}", e)
+        }
+    }
 }
 #[cfg(feature = "tera")]
 /// Sets custom tera configuration.
@@ -102,19 +246,21 @@ pub fn set_tera_config(tera_config: fn(&mut tera::Tera) -> &mut tera::Tera) {
     tera_static.config = Some(tera_config);
     std::mem::drop(tera_static);
 }
-/// Parses settings parameter files.
+/// Parses settings parameter files and keeps the upload limits, so they are read once and not on every upload.
 pub(crate) fn set_config(settings_file_path: &str) {
     let mut config_static = CONFIG.lock().unwrap();
     config_static.collection = kalgan_config::Config::new(&settings_file_path).collection;
     trace!("{:?}", &config_static);
     std::mem::drop(config_static);
+    *UPLOAD_LIMITS.lock().unwrap() = Some(http::upload::Limits::from_settings());
 }
 /// Parses routing files.
 fn set_routes() {
+    let (router, patterns) = handler::routes::load(&settings::get_string("router.path").unwrap());
     let mut route_static = ROUTES.lock().unwrap();
-    route_static.collection =
-        kalgan_router::Router::new(&settings::get_string("router.path").unwrap()).collection;
+    route_static.collection = router.collection;
     trace!("{:?}", &route_static);
+    *ROUTE_PATTERNS.lock().unwrap() = patterns;
     std::mem::drop(route_static);
 }
 #[cfg(feature = "kalgan_i18n")]
@@ -0,0 +1,221 @@
+//! Module for the tokio tcp listener of the async http server.
+
+use crate::{
+    handler::{future::AsyncController, middleware::AsyncMiddlewareFactory, resolver},
+    http::extensions::Extensions,
+    server::{
+        connection::Peer,
+        tcp_listener::{self, ServerHandle},
+    },
+    settings,
+};
+use log::{error, info, warn};
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use tokio::net::TcpListener;
+
+const SHUTDOWN_POLL_INTERVAL: u64 = 10;
+
+/// Binds the tcp listener and serves the incoming connections in the shared tokio runtime.
+/// # Errors
+/// Returns the error message if the tcp listener cannot be bound or tls is configured,
+/// since the async server does not terminate tls connections.
+pub(crate) fn start(
+    settings_file_path: &'static str,
+    controller: AsyncController,
+    middleware: Option<Arc<dyn AsyncMiddlewareFactory>>,
+    state: Arc<Extensions>,
+) -> Result<ServerHandle, String> {
+    if settings::exists("server.tls.cert") {
+        return Err(
+            "TLS is not supported by the async server, use kalgan::start instead.".to_string(),
+        );
+    }
+    let listener = tcp_listener::bind()?;
+    let address = listener.local_addr().map_err(|e| e.to_string())?;
+    info!("Listening on {}", address);
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&shutdown);
+    let thread = thread::spawn(move || {
+        crate::runtime().block_on(serve(
+            listener,
+            settings_file_path,
+            controller,
+            middleware,
+            state,
+            flag,
+        ));
+    });
+    Ok(ServerHandle::new(address, shutdown, thread))
+}
+/// Accepts connections until the shutdown flag is set and then waits for the connections in flight.
+async fn serve(
+    listener: std::net::TcpListener,
+    settings_file_path: &'static str,
+    controller: AsyncController,
+    middleware: Option<Arc<dyn AsyncMiddlewareFactory>>,
+    state: Arc<Extensions>,
+    shutdown: Arc<AtomicBool>,
+) {
+    let listener = match listener
+        .set_nonblocking(true)
+        .and_then(|_| TcpListener::from_std(listener))
+    {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("App cannot be started.");
+            error!("{}", e);
+            return;
+        }
+    };
+    let connections = Arc::new(AtomicUsize::new(0));
+    loop {
+        let accepted = listener.accept().await;
+        if shutdown.load(Ordering::SeqCst) {
+            break;
+        }
+        match accepted {
+            Ok((stream, addr)) => {
+                let counter = Arc::clone(&connections);
+                let flag = Arc::clone(&shutdown);
+                let state = Arc::clone(&state);
+                let middleware = middleware.clone();
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    resolver::execute_async(
+                        settings_file_path,
+                        stream,
+                        Peer {
+                            addr: Some(addr),
+                            secure: false,
+                        },
+                        controller,
+                        middleware.as_deref(),
+                        &state,
+                        &flag,
+                    )
+                    .await;
+                    counter.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+            Err(e) => error!("{}", e),
+        }
+    }
+    std::mem::drop(listener);
+    info!("Shutting down the http server...");
+    let deadline = Instant::now() + Duration::from_secs(settings::shutdown_timeout());
+    while connections.load(Ordering::SeqCst) > 0 && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(SHUTDOWN_POLL_INTERVAL)).await;
+    }
+    match connections.load(Ordering::SeqCst) {
+        0 => info!("The http server has been stopped."),
+        n => warn!(
+            "{} connections did not finish before the shutdown timeout.",
+            n
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        handler::future::BoxFuture,
+        http::{request::Request, response::Response},
+    };
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+    };
+
+    struct Name(String);
+
+    async fn hello_world(_request: &Request) -> Result<Response, String> {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        Ok(Response::new().set_status(200).set_content("Hello World"))
+    }
+    fn controller<'a>(
+        request: &'a Request,
+        controller: &'a str,
+    ) -> BoxFuture<'a, Result<Response, String>> {
+        Box::pin(async move {
+            match controller {
+                "hello_world" => hello_world(request).await,
+                "upload" => {
+                    let file = &request.get_files()["file"];
+                    Ok(Response::new().set_status(200).set_content(&format!(
+                        "{} {}",
+                        file.filename,
+                        file.path().is_some()
+                    )))
+                }
+                "client" => {
+                    let name = request.state::<Name>().ok_or("App state not found.")?;
+                    Ok(Response::new().set_status(200).set_content(&name.0))
+                }
+                _ => Err(format!("Controller {} not found in resolver.", &controller)),
+            }
+        })
+    }
+
+    #[test]
+    fn test_start() {
+        crate::tests::set_config();
+        crate::mock_routes();
+        let server = start("tests/mock/settings.yaml", controller, None, Arc::default()).unwrap();
+        let mut client = TcpStream::connect(server.local_addr()).unwrap();
+        client
+            .write_all(b"GET /hello-world HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        server.shutdown();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("Hello World"));
+    }
+    #[test]
+    fn test_start_reads_upload() {
+        crate::tests::set_config();
+        crate::mock_routes();
+        let server = start("tests/mock/settings.yaml", controller, None, Arc::default()).unwrap();
+        let mut client = TcpStream::connect(server.local_addr()).unwrap();
+        let body = "--b\r\nContent-Disposition: form-data; name=\"file\"; filename=\"beach.txt\"\r\n\r\nsun, sand and sea\r\n--b--\r\n";
+        client
+            .write_all(format!("POST /upload HTTP/1.1\r\nContent-Type: multipart/form-data; boundary=b\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n{:X}\r\n{}\r\n0\r\n\r\n", body.len(), body).as_bytes())
+            .unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        server.shutdown();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("beach.txt true"));
+    }
+    #[test]
+    fn test_start_shares_state() {
+        crate::tests::set_config();
+        crate::mock_routes();
+        let mut state = Extensions::new();
+        state.insert(Name("World".to_string()));
+        let server = start(
+            "tests/mock/settings.yaml",
+            controller,
+            None,
+            Arc::new(state),
+        )
+        .unwrap();
+        let mut client = TcpStream::connect(server.local_addr()).unwrap();
+        client
+            .write_all(b"GET /client HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        server.shutdown();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("\r\n\r\nWorld"));
+    }
+}
@@ -0,0 +1,705 @@
+//! Module for the connection which reads the http messages sent through the tcp stream.
+
+use crate::http::{
+    multipart,
+    upload::{Limits, Upload, UploadError, Uploader},
+};
+use log::debug;
+use std::{
+    io::{prelude::*, ErrorKind},
+    net::{SocketAddr, TcpStream},
+    time::Duration,
+};
+
+const READ_CHUNK_SIZE: usize = 4096;
+const WRITE_CHUNK_SIZE: usize = 16384;
+const LAST_CHUNK: &[u8] = b"0\r\n\r\n";
+const REQUEST_TIMEOUT: u64 = 5;
+
+/// The reasons why an http message cannot be read from the stream.
+#[derive(Debug, PartialEq)]
+pub(crate) enum FrameError {
+    /// The body is bigger than the `server.max_body_size` setting.
+    PayloadTooLarge,
+    /// The header block (or a chunk size line) is bigger than the `server.max_header_size` setting.
+    HeaderTooLarge,
+    /// The message framing is not valid.
+    BadRequest(String),
+    /// The body cannot be stored.
+    Internal(String),
+}
+impl From<UploadError> for FrameError {
+    fn from(e: UploadError) -> Self {
+        match e {
+            UploadError::TooLarge => FrameError::PayloadTooLarge,
+            UploadError::Malformed(message) => FrameError::BadRequest(message),
+            UploadError::Io(e) => FrameError::Internal(e.to_string()),
+        }
+    }
+}
+/// The peer of the connection.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Peer {
+    /// The address of the client or the nearest proxy.
+    pub(crate) addr: Option<SocketAddr>,
+    /// Whether the connection is secured by tls.
+    pub(crate) secure: bool,
+}
+/// An http message read from the stream.
+pub(crate) struct Message {
+    /// The bytes of the message (only the header block if the body has been read as an upload).
+    pub(crate) data: Vec<u8>,
+    /// The input data and files of the `multipart/form-data` body.
+    pub(crate) upload: Option<Upload>,
+}
+/// Where the body of the message being read is stored.
+enum Sink {
+    /// The body is kept in memory to be parsed with the request.
+    Buffer(Vec<u8>),
+    /// The `multipart/form-data` body is passed to the uploader as it's received.
+    Upload(Box<Uploader>),
+}
+impl Sink {
+    /// Stores the given bytes of the body.
+    fn feed(&mut self, bytes: &[u8]) -> Result<(), FrameError> {
+        match self {
+            Sink::Buffer(body) => body.extend_from_slice(bytes),
+            Sink::Upload(uploader) => uploader.feed(bytes)?,
+        }
+        Ok(())
+    }
+}
+/// How the end of the body is found.
+enum Framing {
+    /// The number of bytes of the body not yet received.
+    Length(usize),
+    /// The body is sent in chunks.
+    Chunked(Chunks),
+}
+/// The state of the chunked body decoder.
+enum ChunkState {
+    /// Waiting for the line with the size of the next chunk.
+    Size,
+    /// Reading the data of a chunk, with the number of bytes not yet received.
+    Data(usize),
+    /// Waiting for the CRLF which follows the data of a chunk.
+    DataEnd,
+    /// Reading the trailer fields which follow the last chunk.
+    Trailer,
+}
+/// Decodes a chunked body as it's received, passing the data of the chunks to the sink.
+struct Chunks {
+    state: ChunkState,
+    size: usize,
+}
+impl Chunks {
+    /// Creates and returns an instance of `Chunks` waiting for the first chunk.
+    fn new() -> Chunks {
+        Chunks {
+            state: ChunkState::Size,
+            size: 0,
+        }
+    }
+    /// Decodes the chunks at the beginning of the buffer.
+    /// Returns whether the whole body has been received and the number of bytes of the buffer consumed.
+    fn decode(
+        &mut self,
+        buffer: &[u8],
+        sink: &mut Sink,
+        max_size: usize,
+        max_line_size: usize,
+    ) -> Result<(bool, usize), FrameError> {
+        let mut position = 0;
+        loop {
+            match self.state {
+                ChunkState::Size => {
+                    let line_end = match find_within(&buffer[position..], b"\r\n", max_line_size)? {
+                        Some(line_end) => position + line_end,
+                        None => return Ok((false, position)),
+                    };
+                    let line = String::from_utf8_lossy(&buffer[position..line_end]);
+                    let size_field = line.split(';').next().unwrap_or("").trim();
+                    let size = usize::from_str_radix(size_field, 16).map_err(|_| {
+                        FrameError::BadRequest(format!("Invalid chunk size \"{}\".", size_field))
+                    })?;
+                    position = line_end + 2;
+                    if size == 0 {
+                        self.state = ChunkState::Trailer;
+                    } else if size > max_size - self.size {
+                        return Err(FrameError::PayloadTooLarge);
+                    } else {
+                        self.size += size;
+                        self.state = ChunkState::Data(size);
+                    }
+                }
+                ChunkState::Data(remaining) => {
+                    let length = remaining.min(buffer.len() - position);
+                    if length == 0 {
+                        return Ok((false, position));
+                    }
+                    sink.feed(&buffer[position..position + length])?;
+                    position += length;
+                    self.state = match remaining - length {
+                        0 => ChunkState::DataEnd,
+                        remaining => ChunkState::Data(remaining),
+                    };
+                }
+                ChunkState::DataEnd => {
+                    if buffer.len() < position + 2 {
+                        return Ok((false, position));
+                    }
+                    if &buffer[position..position + 2] != b"\r\n" {
+                        return Err(FrameError::BadRequest(
+                            "Chunk data is not followed by CRLF.".to_string(),
+                        ));
+                    }
+                    position += 2;
+                    self.state = ChunkState::Size;
+                }
+                // The last chunk is followed by optional trailer fields and a blank line.
+                ChunkState::Trailer => {
+                    let line_end = match find_within(&buffer[position..], b"\r\n", max_line_size)? {
+                        Some(line_end) => position + line_end,
+                        None => return Ok((false, position)),
+                    };
+                    let blank = line_end == position;
+                    position = line_end + 2;
+                    if blank {
+                        return Ok((true, position));
+                    }
+                }
+            }
+        }
+    }
+}
+/// The message whose header block has been received and whose body is being read.
+struct Body {
+    header: Vec<u8>,
+    framing: Framing,
+    sink: Sink,
+    max_size: usize,
+}
+impl Body {
+    /// Returns the message once its body has been read.
+    /// Chunked bodies are returned with a `Content-Length` header instead of the `Transfer-Encoding` one.
+    fn finish(self) -> Result<Message, FrameError> {
+        let header = match &self.framing {
+            Framing::Length(_) => self.header,
+            Framing::Chunked(chunks) => {
+                rewrite_header(&String::from_utf8_lossy(&self.header), chunks.size).into_bytes()
+            }
+        };
+        Ok(match self.sink {
+            Sink::Upload(uploader) => Message {
+                data: header,
+                upload: Some(uploader.finish()?),
+            },
+            Sink::Buffer(body) => {
+                let mut data = header;
+                data.extend_from_slice(&body);
+                Message { data, upload: None }
+            }
+        })
+    }
+}
+/// Splits the bytes read from the stream into http messages.
+/// The bodies are taken from the buffer as they are received, so the bytes read are only looked at once,
+/// and `multipart/form-data` bodies are passed to the uploader instead of being kept in memory.
+struct Framer {
+    buffer: Vec<u8>,
+    max_header_size: usize,
+    max_body_size: usize,
+    body: Option<Body>,
+}
+impl Framer {
+    /// Creates and returns an instance of `Framer` with the given header block and body size limits.
+    fn new(max_header_size: usize, max_body_size: usize) -> Framer {
+        Framer {
+            buffer: Vec::new(),
+            max_header_size,
+            max_body_size,
+            body: None,
+        }
+    }
+    /// Checks whether there are bytes of a message not yet returned.
+    fn is_empty(&self) -> bool {
+        self.buffer.is_empty() && self.body.is_none()
+    }
+    /// Adds the bytes read from the stream.
+    fn extend(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+    /// Returns the next message if it has been received completely.
+    fn next_message(&mut self) -> Result<Option<Message>, FrameError> {
+        if self.body.is_none() && !self.start_body()? {
+            return Ok(None);
+        }
+        self.read_body()
+    }
+    #[cfg(feature = "async")]
+    /// Checks whether the body being read is passed to the uploader, which may write it to disk.
+    fn is_uploading(&self) -> bool {
+        matches!(
+            self.body,
+            Some(Body {
+                sink: Sink::Upload(_),
+                ..
+            })
+        )
+    }
+    /// Passes the bytes of the buffer to the body being read and returns the message once it's complete.
+    fn read_body(&mut self) -> Result<Option<Message>, FrameError> {
+        let body = match self.body.as_mut() {
+            Some(body) => body,
+            None => return Ok(None),
+        };
+        let (complete, length) = match &mut body.framing {
+            Framing::Length(remaining) => {
+                let length = (*remaining).min(self.buffer.len());
+                body.sink.feed(&self.buffer[..length])?;
+                *remaining -= length;
+                (*remaining == 0, length)
+            }
+            Framing::Chunked(chunks) => chunks.decode(
+                &self.buffer,
+                &mut body.sink,
+                body.max_size,
+                self.max_header_size,
+            )?,
+        };
+        self.buffer.drain(..length);
+        if !complete {
+            return Ok(None);
+        }
+        self.body.take().unwrap().finish().map(Some)
+    }
+    /// Takes the header block at the beginning of the buffer and starts reading the body of its message.
+    /// Returns `false` if the header block has not been received completely yet.
+    /// The body of `multipart/form-data` messages is limited by `server.upload.max_size`
+    /// instead of `server.max_body_size`.
+    fn start_body(&mut self) -> Result<bool, FrameError> {
+        let header_length = match find_within(&self.buffer, b"\r\n\r\n", self.max_header_size)? {
+            Some(position) => position + 4,
+            None => return Ok(false),
+        };
+        let header: Vec<u8> = self.buffer.drain(..header_length).collect();
+        let text = String::from_utf8_lossy(&header).to_string();
+        let (sink, max_size) = match get_boundary(&text) {
+            Some(boundary) => {
+                let limits = Limits::get();
+                let max_size = limits.max_size;
+                (
+                    Sink::Upload(Box::new(Uploader::new(&boundary, limits))),
+                    max_size,
+                )
+            }
+            None => (Sink::Buffer(Vec::new()), self.max_body_size),
+        };
+        let framing = if is_chunked(&text) {
+            Framing::Chunked(Chunks::new())
+        } else {
+            let content_length = get_content_length(&text)?;
+            if content_length > max_size {
+                return Err(FrameError::PayloadTooLarge);
+            }
+            Framing::Length(content_length)
+        };
+        self.body = Some(Body {
+            header,
+            framing,
+            sink,
+            max_size,
+        });
+        Ok(true)
+    }
+}
+/// Wraps the stream (a plain tcp stream or a tls stream) and keeps the bytes already read but not yet consumed,
+/// so several requests can be read one after another from the same stream.
+pub(crate) struct Connection<S: Read + Write> {
+    stream: S,
+    framer: Framer,
+}
+impl<S: Read + Write> Connection<S> {
+    /// Creates and returns an instance of `Connection` with the given header block and body size limits.
+    pub(crate) fn new(stream: S, max_header_size: usize, max_body_size: usize) -> Connection<S> {
+        Connection {
+            stream,
+            framer: Framer::new(max_header_size, max_body_size),
+        }
+    }
+    /// Returns the next http message of the stream.
+    /// `None` is returned when the peer closes the connection or the idle timeout expires.
+    pub(crate) fn read_message(&mut self) -> Option<Result<Message, FrameError>> {
+        let mut chunk = [0; READ_CHUNK_SIZE];
+        loop {
+            match self.framer.next_message() {
+                Ok(Some(message)) => return Some(Ok(message)),
+                Ok(None) => (),
+                Err(e) => return Some(Err(e)),
+            }
+            match self.stream.read(&mut chunk) {
+                Ok(0) => {
+                    if !self.framer.is_empty() {
+                        debug!("Connection closed with an incomplete request.");
+                    }
+                    return None;
+                }
+                Ok(n) => self.framer.extend(&chunk[..n]),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    debug!("{}", e);
+                    return None;
+                }
+            }
+        }
+    }
+    /// Writes the given bytes in the tcp stream.
+    pub(crate) fn write(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.stream.write_all(bytes)?;
+        self.stream.flush()
+    }
+    /// Writes the content of the reader in the tcp stream as it's read, in chunks if `chunked` is set.
+    pub(crate) fn write_stream(
+        &mut self,
+        reader: &mut dyn Read,
+        chunked: bool,
+    ) -> std::io::Result<()> {
+        let mut chunk = [0; WRITE_CHUNK_SIZE];
+        loop {
+            let n = match reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if chunked {
+                self.stream.write_all(&encode_chunk(&chunk[..n]))?;
+            } else {
+                self.stream.write_all(&chunk[..n])?;
+            }
+        }
+        if chunked {
+            self.stream.write_all(LAST_CHUNK)?;
+        }
+        self.stream.flush()
+    }
+}
+/// Returns the time the connection waits for the bytes of a request: the keep-alive timeout,
+/// or `REQUEST_TIMEOUT` if keep-alive is disabled.
+pub(crate) fn get_idle_timeout(keep_alive_timeout: u64) -> u64 {
+    if keep_alive_timeout > 0 {
+        keep_alive_timeout
+    } else {
+        REQUEST_TIMEOUT
+    }
+}
+/// Sets the time the tcp stream waits for the next request before the connection is closed.
+pub(crate) fn set_idle_timeout(stream: &TcpStream, idle_timeout: u64) {
+    if let Err(e) = stream.set_read_timeout(Some(Duration::from_secs(idle_timeout))) {
+        debug!("{}", e);
+    }
+}
+#[cfg(feature = "async")]
+/// Wraps the tokio tcp stream and keeps the bytes already read but not yet consumed,
+/// so several requests can be read one after another from the same stream.
+pub(crate) struct AsyncConnection {
+    stream: tokio::net::TcpStream,
+    framer: Framer,
+    idle_timeout: u64,
+}
+#[cfg(feature = "async")]
+impl AsyncConnection {
+    /// Creates and returns an instance of `AsyncConnection` with the given idle timeout
+    /// and header block and body size limits.
+    pub(crate) fn new(
+        stream: tokio::net::TcpStream,
+        idle_timeout: u64,
+        max_header_size: usize,
+        max_body_size: usize,
+    ) -> AsyncConnection {
+        AsyncConnection {
+            stream,
+            framer: Framer::new(max_header_size, max_body_size),
+            idle_timeout,
+        }
+    }
+    /// Returns the next http message of the stream.
+    /// `None` is returned when the peer closes the connection or the idle timeout expires.
+    pub(crate) async fn read_message(&mut self) -> Option<Result<Message, FrameError>> {
+        use tokio::io::AsyncReadExt;
+        let mut chunk = [0; READ_CHUNK_SIZE];
+        loop {
+            match self.next_message().await {
+                Ok(Some(message)) => return Some(Ok(message)),
+                Ok(None) => (),
+                Err(e) => return Some(Err(e)),
+            }
+            let read = self.stream.read(&mut chunk);
+            let result =
+                match tokio::time::timeout(Duration::from_secs(self.idle_timeout), read).await {
+                    Ok(result) => result,
+                    Err(e) => {
+                        debug!("{}", e);
+                        return None;
+                    }
+                };
+            match result {
+                Ok(0) => {
+                    if !self.framer.is_empty() {
+                        debug!("Connection closed with an incomplete request.");
+                    }
+                    return None;
+                }
+                Ok(n) => self.framer.extend(&chunk[..n]),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    debug!("{}", e);
+                    return None;
+                }
+            }
+        }
+    }
+    /// Returns the next message of the framer.
+    /// Uploads are read in the blocking thread pool of tokio since their files may be written to disk.
+    async fn next_message(&mut self) -> Result<Option<Message>, FrameError> {
+        if self.framer.body.is_none() && !self.framer.start_body()? {
+            return Ok(None);
+        }
+        if !self.framer.is_uploading() {
+            return self.framer.read_body();
+        }
+        let mut framer = std::mem::replace(&mut self.framer, Framer::new(0, 0));
+        let (framer, result) = tokio::task::spawn_blocking(move || {
+            let result = framer.read_body();
+            (framer, result)
+        })
+        .await
+        .map_err(|e| FrameError::Internal(e.to_string()))?;
+        self.framer = framer;
+        result
+    }
+    /// Writes the given bytes in the tcp stream.
+    pub(crate) async fn write(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        use tokio::io::AsyncWriteExt;
+        self.stream.write_all(bytes).await?;
+        self.stream.flush().await
+    }
+    /// Writes the content of the reader in the tokio tcp stream as it's read, in chunks if `chunked` is set.
+    /// The reader is blocking so it's read without stalling the other tasks of the runtime.
+    pub(crate) async fn write_stream(
+        &mut self,
+        reader: &mut (dyn Read + Send),
+        chunked: bool,
+    ) -> std::io::Result<()> {
+        use tokio::io::AsyncWriteExt;
+        let mut chunk = [0; WRITE_CHUNK_SIZE];
+        loop {
+            let n = match tokio::task::block_in_place(|| reader.read(&mut chunk)) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if chunked {
+                self.stream.write_all(&encode_chunk(&chunk[..n])).await?;
+            } else {
+                self.stream.write_all(&chunk[..n]).await?;
+            }
+        }
+        if chunked {
+            self.stream.write_all(LAST_CHUNK).await?;
+        }
+        self.stream.flush().await
+    }
+}
+/// Returns the given bytes framed as a chunk of the chunked transfer encoding.
+fn encode_chunk(bytes: &[u8]) -> Vec<u8> {
+    let mut chunk = format!("{:X}\r\n", bytes.len()).into_bytes();
+    chunk.extend_from_slice(bytes);
+    chunk.extend_from_slice(b"\r\n");
+    chunk
+}
+/// Returns the position of the given needle in the buffer.
+fn find(buffer: &[u8], needle: &[u8]) -> Option<usize> {
+    buffer
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+/// Returns the position of the given needle within the first `max_size` bytes of the buffer.
+/// `None` is returned if it has not been received yet.
+/// # Errors
+/// Returns `FrameError::HeaderTooLarge` if the buffer already holds `max_size` bytes without the needle.
+fn find_within(buffer: &[u8], needle: &[u8], max_size: usize) -> Result<Option<usize>, FrameError> {
+    match find(&buffer[..buffer.len().min(max_size)], needle) {
+        Some(position) => Ok(Some(position)),
+        None if buffer.len() >= max_size => Err(FrameError::HeaderTooLarge),
+        None => Ok(None),
+    }
+}
+/// Returns the header fields of the header block as (name, value) pairs.
+fn get_fields(header: &str) -> impl Iterator<Item = (&str, &str)> {
+    header.split("\r\n").skip(1).filter_map(|line| {
+        line.find(':')
+            .map(|position| (line[..position].trim(), line[position + 1..].trim()))
+    })
+}
+/// Returns the boundary of the body if it's a `multipart/form-data` one.
+fn get_boundary(header: &str) -> Option<String> {
+    get_fields(header)
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+        .and_then(|(_, value)| multipart::get_boundary(value))
+}
+/// Checks whether the body is sent with `Transfer-Encoding: chunked`.
+fn is_chunked(header: &str) -> bool {
+    get_fields(header).any(|(name, value)| {
+        name.eq_ignore_ascii_case("transfer-encoding") && value.to_lowercase().contains("chunked")
+    })
+}
+/// Returns the value of the `Content-Length` header or 0 if it's not present.
+fn get_content_length(header: &str) -> Result<usize, FrameError> {
+    match get_fields(header).find(|(name, _)| name.eq_ignore_ascii_case("content-length")) {
+        Some((_, value)) => value
+            .parse()
+            .map_err(|_| FrameError::BadRequest(format!("Invalid Content-Length \"{}\".", value))),
+        None => Ok(0),
+    }
+}
+/// Replaces the `Transfer-Encoding` header of a decoded chunked message with its final `Content-Length`.
+fn rewrite_header(header: &str, content_length: usize) -> String {
+    let mut lines: Vec<&str> = header
+        .trim_end_matches("\r\n")
+        .split("\r\n")
+        .filter(|line| {
+            let name = line.split(':').next().unwrap_or("").trim();
+            !name.eq_ignore_ascii_case("transfer-encoding")
+                && !name.eq_ignore_ascii_case("content-length")
+        })
+        .collect();
+    let content_length = format!("Content-Length: {}", content_length);
+    lines.push(&content_length);
+    format!("{}\r\n\r\n", lines.join("\r\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_message(
+        buffer: &[u8],
+        max_body_size: usize,
+    ) -> Result<Option<(Vec<u8>, usize)>, FrameError> {
+        let mut framer = Framer::new(1024, max_body_size);
+        framer.extend(buffer);
+        Ok(framer
+            .next_message()?
+            .map(|message| (message.data, buffer.len() - framer.buffer.len())))
+    }
+    fn get_length(buffer: &[u8]) -> Option<usize> {
+        parse_message(buffer, 1024)
+            .unwrap()
+            .map(|(_, length)| length)
+    }
+
+    #[test]
+    fn test_parse_message_without_body() {
+        let message = b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        assert_eq!(get_length(message), Some(message.len()));
+    }
+    #[test]
+    fn test_parse_message_with_body() {
+        let message = b"POST / HTTP/1.1\r\ncontent-length: 7\r\n\r\nfoo=bar";
+        assert_eq!(get_length(message), Some(message.len()));
+        assert_eq!(get_length(&message[..message.len() - 1]), None);
+    }
+    #[test]
+    fn test_parse_message_with_pipelined_requests() {
+        let messages = b"GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\n\r\n";
+        assert_eq!(get_length(messages), Some(19));
+    }
+    #[test]
+    fn test_parse_message_with_incomplete_header() {
+        assert_eq!(get_length(b"GET / HTTP/1.1\r\nHost: local"), None);
+    }
+    #[test]
+    fn test_parse_message_with_chunked_body() {
+        let message = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nfoo\r\n4;ext=1\r\n=bar\r\n0\r\n\r\nGET";
+        let (decoded, length) = parse_message(message, 1024).unwrap().unwrap();
+        assert_eq!(length, message.len() - 3);
+        assert_eq!(
+            String::from_utf8(decoded).unwrap(),
+            "POST / HTTP/1.1\r\nContent-Length: 7\r\n\r\nfoo=bar"
+        );
+        assert_eq!(get_length(&message[..60]), None);
+    }
+    #[test]
+    fn test_next_message_with_chunked_body_in_pieces() {
+        let message = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nfoo\r\n4\r\n=bar\r\n0\r\nExpires: never\r\n\r\n";
+        let mut framer = Framer::new(1024, 1024);
+        for byte in &message[..message.len() - 1] {
+            framer.extend(&[*byte]);
+            assert!(framer.next_message().unwrap().is_none());
+        }
+        framer.extend(b"\n");
+        let decoded = framer.next_message().unwrap().unwrap();
+        assert_eq!(
+            String::from_utf8(decoded.data).unwrap(),
+            "POST / HTTP/1.1\r\nContent-Length: 7\r\n\r\nfoo=bar"
+        );
+        assert!(framer.is_empty());
+    }
+    #[test]
+    fn test_parse_message_with_too_large_header() {
+        let mut message = b"GET / HTTP/1.1\r\nCookie: ".to_vec();
+        message.resize(1024, b'a');
+        assert_eq!(
+            parse_message(&message, 1024),
+            Err(FrameError::HeaderTooLarge)
+        );
+        assert_eq!(get_length(&message[..1023]), None);
+        let mut message = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n1;".to_vec();
+        message.resize(2048, b'a');
+        assert_eq!(
+            parse_message(&message, 1024),
+            Err(FrameError::HeaderTooLarge)
+        );
+    }
+    #[test]
+    fn test_get_idle_timeout() {
+        assert_eq!(get_idle_timeout(10), 10);
+        assert_eq!(get_idle_timeout(0), REQUEST_TIMEOUT);
+    }
+    #[test]
+    fn test_parse_message_with_too_large_body() {
+        assert_eq!(
+            parse_message(b"POST / HTTP/1.1\r\nContent-Length: 2048\r\n\r\n", 1024),
+            Err(FrameError::PayloadTooLarge)
+        );
+        assert_eq!(
+            parse_message(
+                b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n800\r\n",
+                1024
+            ),
+            Err(FrameError::PayloadTooLarge)
+        );
+    }
+    #[test]
+    fn test_parse_message_with_invalid_framing() {
+        assert!(matches!(
+            parse_message(b"POST / HTTP/1.1\r\nContent-Length: abc\r\n\r\n", 1024),
+            Err(FrameError::BadRequest(_))
+        ));
+        assert!(matches!(
+            parse_message(
+                b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n",
+                1024
+            ),
+            Err(FrameError::BadRequest(_))
+        ));
+    }
+    #[test]
+    fn test_write_stream_in_chunks() {
+        let mut connection = Connection::new(std::io::Cursor::new(Vec::new()), 1024, 1024);
+        connection.write_stream(&mut &b"foo=bar"[..], true).unwrap();
+        assert_eq!(connection.stream.into_inner(), b"7\r\nfoo=bar\r\n0\r\n\r\n");
+    }
+}
@@ -1,39 +1,258 @@
 //! Module for the tcp listener of the http server.
 
+#[cfg(feature = "tls")]
+use crate::server::tls;
 use crate::{
-    handler::{middleware::Outcome, resolver},
-    http::{request::Request, response::Response},
-    server::thread_pool::ThreadPool,
+    handler::{controller::ControllerFactory, middleware::MiddlewareFactory, resolver},
+    http::extensions::Extensions,
+    server::{
+        connection::{self, Peer},
+        thread_pool::ThreadPool,
+    },
     settings,
 };
-use log::error;
-use std::net::TcpListener;
+use log::{error, info, warn};
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
 
+/// The handle of a running http server returned by `kalgan::start` (or `kalgan::start_async`).
+/// # Examples
+/// ```no_run
+/// use kalgan::http::{request::Request, response::Response};
+///
+/// fn resolver(_request: &Request, controller: &str) -> Result<Response, String> {
+///     Err(format!("Controller {} not found in resolver.", &controller))
+/// }
+/// let server = kalgan::start("settings.yaml", resolver, None).unwrap();
+/// // ...
+/// server.shutdown();
+/// ```
+pub struct ServerHandle {
+    address: SocketAddr,
+    shutdown: Arc<AtomicBool>,
+    thread: thread::JoinHandle<()>,
+}
+impl ServerHandle {
+    /// Creates and returns an instance of `ServerHandle` for the server running in the given thread.
+    pub(crate) fn new(
+        address: SocketAddr,
+        shutdown: Arc<AtomicBool>,
+        thread: thread::JoinHandle<()>,
+    ) -> ServerHandle {
+        ServerHandle {
+            address,
+            shutdown,
+            thread,
+        }
+    }
+    /// Returns the address the server is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+    /// Stops accepting connections, lets the workers finish the requests in flight
+    /// within `server.shutdown_timeout` seconds and returns once the server is stopped.
+    pub fn shutdown(self) {
+        stop(self.address, &self.shutdown);
+        self.wait();
+    }
+    /// Blocks the current thread until the server is stopped.
+    pub fn wait(self) {
+        if self.thread.join().is_err() {
+            error!("The http server stopped unexpectedly.");
+        }
+    }
+    /// Returns the flag that stops the server when it is set to `true`.
+    pub(crate) fn get_shutdown_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.shutdown)
+    }
+}
+/// Binds the tcp listener and serves the incoming connections in a new thread.
+/// # Errors
+/// Returns the error message if the tcp listener cannot be bound or the tls configuration cannot be loaded.
 pub(crate) fn start(
     settings_file_path: &'static str,
-    controller: fn(&Request, &str) -> Result<Response, String>,
-    middleware: Option<fn(&mut Request, &str) -> Result<Outcome, String>>,
-) {
-    match TcpListener::bind(format!(
-        "{}:{}",
-        settings::get_string("server.address").unwrap(),
-        settings::get_number("server.port").unwrap()
-    )) {
-        Ok(listener) => {
-            let pool = ThreadPool::new();
-            for stream in listener.incoming() {
-                let stream = stream.unwrap();
-                let c: fn(&Request, &str) -> Result<Response, String> = controller.clone();
-                let m: Option<fn(&mut Request, &str) -> Result<Outcome, String>> =
-                    middleware.clone();
-                pool.execute(move || {
-                    resolver::execute(settings_file_path, stream, &c, m.as_ref());
-                });
+    controller: Arc<ControllerFactory>,
+    middleware: Option<Arc<dyn MiddlewareFactory>>,
+    state: Arc<Extensions>,
+) -> Result<ServerHandle, String> {
+    #[cfg(feature = "tls")]
+    let tls_config = tls::get_config()?;
+    let listener = bind()?;
+    let address = listener.local_addr().map_err(|e| e.to_string())?;
+    info!("Listening on {}", address);
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&shutdown);
+    #[cfg(feature = "tls")]
+    let redirect = match settings::get_number("server.tls.redirect_port") {
+        Ok(port) if tls_config.is_some() => Some(tls::start_redirect(
+            port,
+            address.port(),
+            Arc::clone(&shutdown),
+        )?),
+        _ => None,
+    };
+    let thread = thread::spawn(move || {
+        let pool = ThreadPool::new();
+        let idle = pool.get_idle_workers();
+        loop {
+            let accepted = listener.accept();
+            if flag.load(Ordering::SeqCst) {
+                break;
+            }
+            match accepted {
+                Ok((stream, addr)) => {
+                    connection::set_idle_timeout(
+                        &stream,
+                        connection::get_idle_timeout(settings::keep_alive_timeout()),
+                    );
+                    let c = Arc::clone(&controller);
+                    let m = middleware.clone();
+                    let s = Arc::clone(&state);
+                    let f = Arc::clone(&flag);
+                    let i = Arc::clone(&idle);
+                    // A connection only waits for its next request while another worker is free,
+                    // so idle connections cannot hold up the new ones.
+                    let can_keep_alive =
+                        move || !f.load(Ordering::SeqCst) && i.load(Ordering::SeqCst) > 0;
+                    let peer = Peer {
+                        addr: Some(addr),
+                        secure: false,
+                    };
+                    #[cfg(feature = "tls")]
+                    if let Some(config) = &tls_config {
+                        let config = Arc::clone(config);
+                        pool.execute(move || match tls::accept(config, stream) {
+                            Ok(stream) => resolver::execute(
+                                settings_file_path,
+                                stream,
+                                Peer {
+                                    secure: true,
+                                    ..peer
+                                },
+                                c.as_ref(),
+                                m.as_deref(),
+                                &s,
+                                &can_keep_alive,
+                            ),
+                            Err(e) => error!("{}", e),
+                        });
+                        continue;
+                    }
+                    pool.execute(move || {
+                        resolver::execute(
+                            settings_file_path,
+                            stream,
+                            peer,
+                            c.as_ref(),
+                            m.as_deref(),
+                            &s,
+                            &can_keep_alive,
+                        );
+                    });
+                }
+                Err(e) => error!("{}", e),
             }
         }
-        Err(e) => {
-            error!("App cannot be started.");
-            error!("{}", e)
+        info!("Shutting down the http server...");
+        #[cfg(feature = "tls")]
+        if let Some((address, _)) = &redirect {
+            wake(*address);
         }
+        pool.shutdown(Duration::from_secs(settings::shutdown_timeout()));
+        #[cfg(feature = "tls")]
+        if let Some((_, redirect)) = redirect {
+            if redirect.join().is_err() {
+                error!("The https redirect listener stopped unexpectedly.");
+            }
+        }
+        info!("The http server has been stopped.");
+    });
+    Ok(ServerHandle::new(address, shutdown, thread))
+}
+/// Binds the tcp listener set in the settings.
+/// # Errors
+/// Returns the error message if the tcp listener cannot be bound.
+pub(crate) fn bind() -> Result<TcpListener, String> {
+    TcpListener::bind(format!(
+        "{}:{}",
+        settings::get_string("server.address")?,
+        settings::get_number("server.port")?
+    ))
+    .map_err(|e| e.to_string())
+}
+/// Sets the shutdown flag and wakes the listener bound to the given address up.
+pub(crate) fn stop(address: SocketAddr, shutdown: &AtomicBool) {
+    shutdown.store(true, Ordering::SeqCst);
+    wake(address);
+}
+/// Wakes the listener bound to the given address up from a blocking `accept` by connecting to it.
+pub(crate) fn wake(address: SocketAddr) {
+    let address = match address.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => {
+            SocketAddr::new(Ipv4Addr::LOCALHOST.into(), address.port())
+        }
+        IpAddr::V6(ip) if ip.is_unspecified() => {
+            SocketAddr::new(Ipv6Addr::LOCALHOST.into(), address.port())
+        }
+        _ => address,
+    };
+    if let Err(e) = TcpStream::connect(address) {
+        warn!("The listener on {} cannot be woken up: {}", address, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{request::Request, response::Response};
+    use std::io::{Read, Write};
+
+    fn controller(_request: &Request, controller: &str) -> Result<Response, String> {
+        match controller {
+            "hello_world" => {
+                thread::sleep(Duration::from_millis(300));
+                Ok(Response::new().set_status(200).set_content("Hello World"))
+            }
+            _ => Err(format!("Controller {} not found in resolver.", &controller)),
+        }
+    }
+
+    #[test]
+    fn test_shutdown_finishes_requests_in_flight() {
+        crate::tests::set_config();
+        crate::mock_routes();
+        let server = start(
+            "tests/mock/settings.yaml",
+            Arc::new(controller),
+            None,
+            Arc::default(),
+        )
+        .unwrap();
+        let address = server.local_addr();
+        let mut client = TcpStream::connect(address).unwrap();
+        client
+            .write_all(b"GET /hello-world HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        thread::sleep(Duration::from_millis(100));
+        server.shutdown();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(TcpStream::connect(address).is_err());
+    }
+    #[test]
+    fn test_wake_unspecified_address() {
+        let listener = TcpListener::bind("0.0.0.0:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let accept = thread::spawn(move || listener.accept().is_ok());
+        wake(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port));
+        assert!(accept.join().unwrap());
     }
 }
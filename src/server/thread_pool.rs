@@ -5,13 +5,22 @@ use crate::{
     settings,
 };
 use log::{debug, info, warn};
-use std::{sync::mpsc, sync::Arc, sync::Mutex};
+use std::{
+    sync::atomic::AtomicUsize,
+    sync::mpsc,
+    sync::Arc,
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
 
 const NUMBER_OF_WORKERS: usize = 10;
+const SHUTDOWN_POLL_INTERVAL: u64 = 10;
 
 pub(crate) struct ThreadPool {
     workers: Vec<Worker>,
     sender: mpsc::Sender<Message>,
+    idle: Arc<AtomicUsize>,
 }
 impl ThreadPool {
     pub(crate) fn new() -> ThreadPool {
@@ -28,11 +37,20 @@ impl ThreadPool {
         };
         let (sender, receiver) = mpsc::channel();
         let receiver = Arc::new(Mutex::new(receiver));
+        let idle = Arc::new(AtomicUsize::new(0));
         let mut workers = Vec::with_capacity(size);
         for id in 0..size {
-            workers.push(Worker::new(id, Arc::clone(&receiver)));
+            workers.push(Worker::new(id, Arc::clone(&receiver), Arc::clone(&idle)));
+        }
+        ThreadPool {
+            workers,
+            sender,
+            idle,
         }
-        ThreadPool { workers, sender }
+    }
+    /// Returns the number of workers waiting for a job.
+    pub(crate) fn get_idle_workers(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.idle)
     }
     pub(crate) fn execute<F>(&self, f: F)
     where
@@ -41,19 +59,45 @@ impl ThreadPool {
         let job = Box::new(f);
         self.sender.send(Message::NewJob(job)).unwrap();
     }
-}
-impl Drop for ThreadPool {
-    fn drop(&mut self) {
+    /// Lets the workers finish the queued jobs and shuts them down.
+    /// Workers still busy when the timeout expires are left behind.
+    pub(crate) fn shutdown(mut self, timeout: Duration) {
+        self.terminate(Some(Instant::now() + timeout));
+    }
+    /// Sends the terminate message to all workers and waits for them until the given deadline (if any).
+    fn terminate(&mut self, deadline: Option<Instant>) {
+        if self.workers.is_empty() {
+            return;
+        }
         info!("Sending terminate message to all workers.");
         for _ in &self.workers {
             self.sender.send(Message::Terminate).unwrap();
         }
         info!("Shutting down all workers.");
-        for worker in &mut self.workers {
+        for mut worker in self.workers.drain(..) {
             debug!("Shutting down worker {}", worker.id);
             if let Some(thread) = worker.thread.take() {
-                thread.join().unwrap();
+                if let Some(deadline) = deadline {
+                    while !thread.is_finished() && Instant::now() < deadline {
+                        thread::sleep(Duration::from_millis(SHUTDOWN_POLL_INTERVAL));
+                    }
+                    if !thread.is_finished() {
+                        warn!(
+                            "Worker {} did not finish its job before the shutdown timeout.",
+                            worker.id
+                        );
+                        continue;
+                    }
+                }
+                if thread.join().is_err() {
+                    warn!("Worker {} panicked while shutting down.", worker.id);
+                }
             }
         }
     }
 }
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.terminate(None);
+    }
+}
@@ -0,0 +1,271 @@
+//! Module for the tls termination of the http server based on [rustls crate v0.21](https://docs.rs/rustls/0.21/rustls/).
+
+use crate::{
+    http::{request::Request, response::Response},
+    server::connection::{self, Connection},
+    settings,
+};
+use log::{debug, error, info, warn};
+use rustls::{Certificate, PrivateKey, ServerConfig, ServerConnection, StreamOwned};
+use rustls_pemfile::Item;
+use std::{
+    fs::File,
+    io::BufReader,
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+const HTTPS_PORT: u16 = 443;
+const REDIRECT_TIMEOUT: u64 = 5;
+
+/// Returns the tls configuration if `server.tls.cert` and `server.tls.key` are set.
+/// # Errors
+/// Returns the error message if the certificate or the private key cannot be loaded.
+pub(crate) fn get_config() -> Result<Option<Arc<ServerConfig>>, String> {
+    if settings::exists("server.tls.cert") {
+        Ok(Some(load_config(
+            &settings::get_string("server.tls.cert")?,
+            &settings::get_string("server.tls.key")?,
+        )?))
+    } else {
+        Ok(None)
+    }
+}
+/// Returns the tls configuration for the given PEM certificate chain and private key files.
+/// # Errors
+/// Returns the error message if the certificate or the private key cannot be loaded.
+pub(crate) fn load_config(cert_path: &str, key_path: &str) -> Result<Arc<ServerConfig>, String> {
+    let certs = rustls_pemfile::certs(&mut open(cert_path)?)
+        .map_err(|e| format!("Certificate \"{}\" cannot be parsed: {}", cert_path, e))?;
+    if certs.is_empty() {
+        return Err(format!("No certificate found in \"{}\".", cert_path));
+    }
+    let key = load_private_key(key_path)?;
+    let config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs.into_iter().map(Certificate).collect(), key)
+        .map_err(|e| e.to_string())?;
+    info!("TLS is enabled with certificate \"{}\".", cert_path);
+    Ok(Arc::new(config))
+}
+/// Wraps the tcp stream in a tls stream.
+/// # Errors
+/// Returns the error message if the tls session cannot be created.
+pub(crate) fn accept(
+    config: Arc<ServerConfig>,
+    stream: TcpStream,
+) -> Result<StreamOwned<ServerConnection, TcpStream>, String> {
+    let session = ServerConnection::new(config).map_err(|e| e.to_string())?;
+    Ok(StreamOwned::new(session, stream))
+}
+/// Starts the plain http listener on `server.tls.redirect_port` which redirects every request to https
+/// and returns its address and thread.
+/// # Errors
+/// Returns the error message if the tcp listener cannot be bound.
+pub(crate) fn start_redirect(
+    redirect_port: i64,
+    https_port: u16,
+    shutdown: Arc<AtomicBool>,
+) -> Result<(SocketAddr, thread::JoinHandle<()>), String> {
+    let listener = TcpListener::bind(format!(
+        "{}:{}",
+        settings::get_string("server.address")?,
+        redirect_port
+    ))
+    .map_err(|e| e.to_string())?;
+    let address = listener.local_addr().map_err(|e| e.to_string())?;
+    info!(
+        "Redirecting http requests on port {} to https.",
+        redirect_port
+    );
+    let thread = thread::spawn(move || loop {
+        let accepted = listener.accept();
+        if shutdown.load(Ordering::SeqCst) {
+            break;
+        }
+        match accepted {
+            // A client which sends nothing must not hold up the redirection of the others.
+            Ok((stream, _)) => {
+                thread::spawn(move || redirect(stream, https_port));
+            }
+            Err(e) => error!("{}", e),
+        }
+    });
+    Ok((address, thread))
+}
+/// Reads the request sent through the tcp stream and answers with a redirection to the same url on https.
+fn redirect(stream: TcpStream, https_port: u16) {
+    connection::set_idle_timeout(&stream, REDIRECT_TIMEOUT);
+    let mut connection = Connection::new(stream, settings::max_header_size(), 0);
+    if let Some(Ok(message)) = connection.read_message() {
+        if let Some(request) = Request::new(&message.data) {
+            let location = get_location(request.get_host(), request.get_target(), https_port);
+            debug!("Redirecting to {}", &location);
+            let response = Response::new()
+                .set_status(301)
+                .set_location(&location)
+                .set_connection(false, 0);
+            if let Err(e) = connection.write(&response.create()) {
+                warn!("{}", e);
+            }
+        }
+    }
+}
+/// Returns the https url for the given host and request target, which is kept as it was sent (url encoded).
+fn get_location(host: &str, target: &str, https_port: u16) -> String {
+    let host = match host.rfind(':') {
+        Some(position) if !host.ends_with(']') => &host[..position],
+        _ => host,
+    };
+    let uri = if target.starts_with('/') { target } else { "/" };
+    if https_port == HTTPS_PORT {
+        format!("https://{}{}", host, uri)
+    } else {
+        format!("https://{}:{}{}", host, https_port, uri)
+    }
+}
+/// Opens the given file to be read.
+fn open(path: &str) -> Result<BufReader<File>, String> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| format!("File \"{}\" cannot be opened: {}", path, e))
+}
+/// Returns the first private key (PKCS#8, RSA or EC) found in the given PEM file.
+fn load_private_key(key_path: &str) -> Result<PrivateKey, String> {
+    let mut reader = open(key_path)?;
+    loop {
+        match rustls_pemfile::read_one(&mut reader)
+            .map_err(|e| format!("Private key \"{}\" cannot be parsed: {}", key_path, e))?
+        {
+            Some(Item::PKCS8Key(key)) | Some(Item::RSAKey(key)) | Some(Item::ECKey(key)) => {
+                return Ok(PrivateKey(key))
+            }
+            Some(_) => continue,
+            None => return Err(format!("No private key found in \"{}\".", key_path)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handler::resolver, server::connection::Peer};
+    use rustls::{ClientConfig, ClientConnection, RootCertStore};
+    use std::{
+        convert::TryInto,
+        env, fs,
+        io::{ErrorKind, Read, Write},
+        path::PathBuf,
+    };
+
+    fn controller(_request: &Request, controller: &str) -> Result<Response, String> {
+        match controller {
+            "hello_world" => Ok(Response::new().set_status(200).set_content("Hello World")),
+            _ => Err(format!("Controller {} not found in resolver.", &controller)),
+        }
+    }
+    /// Generates a self-signed certificate for localhost and returns the paths of the PEM files and the DER certificate.
+    fn generate_certificate(name: &str) -> (PathBuf, PathBuf, Vec<u8>) {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let dir = env::temp_dir().join(format!("kalgan_tls_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let cert_path = dir.join("cert.pem");
+        let key_path = dir.join("key.pem");
+        fs::write(&cert_path, cert.serialize_pem().unwrap()).unwrap();
+        fs::write(&key_path, cert.serialize_private_key_pem()).unwrap();
+        (cert_path, key_path, cert.serialize_der().unwrap())
+    }
+
+    #[test]
+    fn test_load_config() {
+        let (cert_path, key_path, _) = generate_certificate("load");
+        assert!(load_config(cert_path.to_str().unwrap(), key_path.to_str().unwrap()).is_ok());
+        assert!(load_config(key_path.to_str().unwrap(), key_path.to_str().unwrap()).is_err());
+        assert!(load_config("not_found.pem", key_path.to_str().unwrap()).is_err());
+    }
+    #[test]
+    fn test_accept() {
+        crate::tests::set_config();
+        crate::mock_routes();
+        let (cert_path, key_path, der) = generate_certificate("accept");
+        let config = load_config(cert_path.to_str().unwrap(), key_path.to_str().unwrap()).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, addr) = listener.accept().unwrap();
+            let controller: fn(&Request, &str) -> Result<Response, String> = controller;
+            resolver::execute(
+                "tests/mock/settings.yaml",
+                accept(config, stream).unwrap(),
+                Peer {
+                    addr: Some(addr),
+                    secure: true,
+                },
+                &controller,
+                None,
+                &Arc::default(),
+                &|| true,
+            );
+        });
+        let mut roots = RootCertStore::empty();
+        roots.add(&Certificate(der)).unwrap();
+        let client_config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let session =
+            ClientConnection::new(Arc::new(client_config), "localhost".try_into().unwrap())
+                .unwrap();
+        let mut client = StreamOwned::new(session, TcpStream::connect(address).unwrap());
+        client
+            .write_all(b"GET /hello-world HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut response = Vec::new();
+        if let Err(e) = client.read_to_end(&mut response) {
+            assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+        }
+        server.join().unwrap();
+        let response = String::from_utf8(response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("Hello World"));
+    }
+    #[test]
+    fn test_get_location() {
+        assert_eq!(
+            get_location("localhost:8080", "/hello-world?a=b", 443),
+            "https://localhost/hello-world?a=b"
+        );
+        assert_eq!(
+            get_location("localhost", "/", 8443),
+            "https://localhost:8443/"
+        );
+        assert_eq!(get_location("[::1]", "/", 443), "https://[::1]/");
+        assert_eq!(get_location("localhost", "*", 443), "https://localhost/");
+    }
+    #[test]
+    fn test_redirect_keeps_encoded_target() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            redirect(stream, 443);
+        });
+        let mut client = TcpStream::connect(address).unwrap();
+        client
+            .write_all(b"GET /%0d%0aSet-Cookie:%20x=1?a=%26 HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        server.join().unwrap();
+        assert!(response.starts_with("HTTP/1.1 301 Moved Permanently"));
+        assert!(
+            response.contains("\r\nLocation: https://localhost/%0d%0aSet-Cookie:%20x=1?a=%26\r\n")
+        );
+        assert!(!response.contains("\r\nSet-Cookie"));
+    }
+}
@@ -1,8 +1,12 @@
 //! Module for the workers of the http server.
 
-use log::debug;
+use log::{debug, error};
 use std::{
-    sync::{mpsc, Arc, Mutex},
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
     thread,
 };
 
@@ -16,13 +20,24 @@ pub(crate) enum Message {
     Terminate,
 }
 impl Worker {
-    pub(crate) fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Message>>>) -> Worker {
+    /// Creates and returns an instance of `Worker` which executes the jobs of the receiver,
+    /// counting itself in `idle` while it waits for them.
+    pub(crate) fn new(
+        id: usize,
+        receiver: Arc<Mutex<mpsc::Receiver<Message>>>,
+        idle: Arc<AtomicUsize>,
+    ) -> Worker {
         let thread = thread::spawn(move || loop {
+            idle.fetch_add(1, Ordering::SeqCst);
             let message = receiver.lock().unwrap().recv().unwrap();
+            idle.fetch_sub(1, Ordering::SeqCst);
             match message {
                 Message::NewJob(job) => {
                     debug!("Worker {} got a job; executing.", id);
-                    job();
+                    // A panicking job must not take the worker down with it.
+                    if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                        error!("Worker {} panicked while executing a job.", id);
+                    }
                 }
                 Message::Terminate => {
                     debug!("Worker {} was told to terminate.", id);
@@ -36,3 +51,26 @@ impl Worker {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_worker_survives_panicking_job() {
+        let (sender, receiver) = mpsc::channel();
+        let mut worker = Worker::new(0, Arc::new(Mutex::new(receiver)), Arc::default());
+        let (result_sender, result_receiver) = mpsc::channel();
+        sender
+            .send(Message::NewJob(Box::new(|| panic!("Job failed."))))
+            .unwrap();
+        sender
+            .send(Message::NewJob(Box::new(move || {
+                result_sender.send(1984).unwrap()
+            })))
+            .unwrap();
+        sender.send(Message::Terminate).unwrap();
+        assert_eq!(result_receiver.recv().unwrap(), 1984);
+        assert!(worker.thread.take().unwrap().join().is_ok());
+    }
+}
@@ -1,5 +1,6 @@
 //! A service for route management.
 
+use crate::http::request::Request;
 use std::collections::HashMap;
 
 /// Returns the uri for the given route name.
@@ -37,6 +38,40 @@ pub fn generate(route_name: &str, parameters: HashMap<&str, String>) -> String {
         .get_uri(route_name, parameters)
 }
 
+/// Returns the absolute url for the given route name with the scheme and the host requested by the client.
+/// The `Forwarded`, `X-Forwarded-Proto` and `X-Forwarded-Host` headers are only taken into account
+/// when they are sent by one of the proxies of the `server.trusted_proxies` setting.
+/// # Examples
+/// ```
+/// use kalgan::service::url;
+/// # use kalgan::http::request::{Mock, Request};
+/// # use std::collections::HashMap;
+///
+/// # kalgan::mock_settings("tests/mock/settings.yaml");
+/// # kalgan::mock_routes();
+/// # let request = Request::mock()
+/// #     .mock_set_remote_addr("10.0.0.1:54321".parse().unwrap())
+/// #     .mock_set_host("backend:8080".to_string())
+/// #     .mock_set_headers(vec![
+/// #         ("X-Forwarded-Proto".to_string(), "https".to_string()),
+/// #         ("X-Forwarded-Host".to_string(), "example.com".to_string()),
+/// #     ]);
+/// let hello_world_url: String = url::generate_absolute(&request, "hello_world", HashMap::new());
+/// assert_eq!(hello_world_url, "https://example.com/hello-world".to_string());
+/// ```
+pub fn generate_absolute(
+    request: &Request,
+    route_name: &str,
+    parameters: HashMap<&str, String>,
+) -> String {
+    format!(
+        "{}://{}{}",
+        request.client_scheme(),
+        request.client_host(),
+        generate(route_name, parameters)
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
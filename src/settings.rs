@@ -2,9 +2,18 @@
 
 use kalgan_config::Value;
 use log::warn;
+use std::path::PathBuf;
 
 const REFRESH_CONFIG_TIMEOUT: u64 = 3;
 const IS_PROD: bool = false;
+const KEEP_ALIVE_TIMEOUT: u64 = 5;
+const MAX_REQUESTS_PER_CONNECTION: u64 = 100;
+const MAX_HEADER_SIZE: usize = 16384;
+const MAX_BODY_SIZE: usize = 10240000;
+const SHUTDOWN_TIMEOUT: u64 = 30;
+const UPLOAD_MAX_SIZE: usize = 104857600;
+const UPLOAD_MAX_FIELD_SIZE: usize = 52428800;
+const UPLOAD_SPOOL_THRESHOLD: usize = 1048576;
 
 /// Returns the given settings parameter as `serde_yaml::Value`.
 /// # Errors
@@ -76,6 +85,156 @@ pub(crate) fn is_prod() -> bool {
         }
     }
 }
+/// Returns the `server.keep_alive_timeout` value.
+/// If it doesn't exist it returns `KEEP_ALIVE_TIMEOUT` const.
+pub(crate) fn keep_alive_timeout() -> u64 {
+    get_optional_number("server.keep_alive_timeout", KEEP_ALIVE_TIMEOUT as i64) as u64
+}
+/// Returns the `server.max_requests_per_connection` value.
+/// If it doesn't exist it returns `MAX_REQUESTS_PER_CONNECTION` const.
+pub(crate) fn max_requests_per_connection() -> u64 {
+    get_optional_number(
+        "server.max_requests_per_connection",
+        MAX_REQUESTS_PER_CONNECTION as i64,
+    ) as u64
+}
+/// Returns the `server.max_header_size` value in bytes.
+/// If it doesn't exist it returns `MAX_HEADER_SIZE` const.
+pub(crate) fn max_header_size() -> usize {
+    get_optional_number("server.max_header_size", MAX_HEADER_SIZE as i64) as usize
+}
+/// Returns the `server.max_body_size` value in bytes.
+/// If it doesn't exist it returns `MAX_BODY_SIZE` const.
+pub(crate) fn max_body_size() -> usize {
+    get_optional_number("server.max_body_size", MAX_BODY_SIZE as i64) as usize
+}
+/// Returns the `server.shutdown_timeout` value.
+/// If it doesn't exist it returns `SHUTDOWN_TIMEOUT` const.
+pub(crate) fn shutdown_timeout() -> u64 {
+    match get_number("server.shutdown_timeout") {
+        Ok(num) => num as u64,
+        Err(e) => {
+            warn!("{}", e);
+            warn!(
+                "shutdown_timeout is not defined. {} taken as default.",
+                SHUTDOWN_TIMEOUT
+            );
+            SHUTDOWN_TIMEOUT
+        }
+    }
+}
+/// Returns the `server.upload.max_size` value in bytes (the size limit of `multipart/form-data` bodies).
+/// If it doesn't exist it returns `UPLOAD_MAX_SIZE` const.
+pub(crate) fn upload_max_size() -> usize {
+    match get_number("server.upload.max_size") {
+        Ok(num) => num as usize,
+        Err(e) => {
+            warn!("{}", e);
+            warn!(
+                "upload.max_size is not defined. {} taken as default.",
+                UPLOAD_MAX_SIZE
+            );
+            UPLOAD_MAX_SIZE
+        }
+    }
+}
+/// Returns the `server.upload.max_field_size` value in bytes (the size limit of each field or file of the upload).
+/// If it doesn't exist it returns `UPLOAD_MAX_FIELD_SIZE` const.
+pub(crate) fn upload_max_field_size() -> usize {
+    match get_number("server.upload.max_field_size") {
+        Ok(num) => num as usize,
+        Err(e) => {
+            warn!("{}", e);
+            warn!(
+                "upload.max_field_size is not defined. {} taken as default.",
+                UPLOAD_MAX_FIELD_SIZE
+            );
+            UPLOAD_MAX_FIELD_SIZE
+        }
+    }
+}
+/// Returns the `server.upload.spool_threshold` value in bytes (the size from which uploaded files are written to disk).
+/// If it doesn't exist it returns `UPLOAD_SPOOL_THRESHOLD` const.
+pub(crate) fn upload_spool_threshold() -> usize {
+    match get_number("server.upload.spool_threshold") {
+        Ok(num) => num as usize,
+        Err(e) => {
+            warn!("{}", e);
+            warn!(
+                "upload.spool_threshold is not defined. {} taken as default.",
+                UPLOAD_SPOOL_THRESHOLD
+            );
+            UPLOAD_SPOOL_THRESHOLD
+        }
+    }
+}
+/// Returns the `server.upload.temp_dir` value (the folder of the uploaded files written to disk).
+/// If it doesn't exist it returns the temporary directory of the system.
+pub(crate) fn upload_temp_dir() -> PathBuf {
+    match get_string("server.upload.temp_dir") {
+        Ok(temp_dir) => PathBuf::from(temp_dir),
+        Err(e) => {
+            warn!("{}", e);
+            let temp_dir = std::env::temp_dir();
+            warn!(
+                "upload.temp_dir is not defined. {} taken as default.",
+                temp_dir.display()
+            );
+            temp_dir
+        }
+    }
+}
+/// Returns the `server.trusted_proxies` value (the ip addresses or CIDR ranges of the reverse proxies).
+/// If it doesn't exist it returns an empty list.
+pub(crate) fn trusted_proxies() -> Vec<String> {
+    get_optional_vec(
+        "server.trusted_proxies",
+        "trusted_proxies is not a list. No proxy is trusted.",
+    )
+}
+/// Returns the `router.middleware` value (the middleware called before the ones of every route).
+/// If it doesn't exist it returns an empty list.
+pub(crate) fn global_middleware() -> Vec<String> {
+    get_optional_vec(
+        "router.middleware",
+        "router.middleware is not a list. No middleware is called for every route.",
+    )
+}
+/// Returns the given number parameter, which is optional: nothing is reported if it doesn't exist,
+/// since the optional parameters are read on every connection or request.
+/// If it isn't a number, the error is reported and it returns the given default.
+fn get_optional_number(key: &str, default: i64) -> i64 {
+    if !exists(key) {
+        return default;
+    }
+    match get_number(key) {
+        Ok(num) => num,
+        Err(e) => {
+            warn!("{}", e);
+            warn!("{} is not a number. {} taken as default.", key, default);
+            default
+        }
+    }
+}
+/// Returns the strings of the given list parameter, which is optional: nothing is reported if it doesn't exist,
+/// since the optional parameters are read on every request.
+/// If it isn't a list, the error and the given fallback message are reported and it returns an empty list.
+fn get_optional_vec(key: &str, fallback: &str) -> Vec<String> {
+    if !exists(key) {
+        return Vec::new();
+    }
+    match get_vec(key) {
+        Ok(values) => values
+            .iter()
+            .filter_map(|value| value.as_str().map(|value| value.to_string()))
+            .collect(),
+        Err(e) => {
+            warn!("{}", e);
+            warn!("{}", fallback);
+            Vec::new()
+        }
+    }
+}
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -128,4 +287,48 @@ mod tests {
         crate::tests::set_config();
         assert_eq!(is_prod(), false);
     }
+    #[test]
+    fn test_keep_alive_timeout() {
+        crate::tests::set_config();
+        assert_eq!(keep_alive_timeout(), 5);
+    }
+    #[test]
+    fn test_max_requests_per_connection() {
+        crate::tests::set_config();
+        assert_eq!(max_requests_per_connection(), 100);
+    }
+    #[test]
+    fn test_max_body_size() {
+        crate::tests::set_config();
+        assert_eq!(max_body_size(), 1048576);
+    }
+    #[test]
+    fn test_get_optional_number() {
+        crate::tests::set_config();
+        assert_eq!(get_optional_number("server.keep_alive_timeout", 1), 5);
+        assert_eq!(get_optional_number("server.unknown_timeout", 1), 1);
+        assert_eq!(get_optional_number("mock.string_value", 1), 1);
+    }
+    #[test]
+    fn test_shutdown_timeout() {
+        crate::tests::set_config();
+        assert_eq!(shutdown_timeout(), 10);
+    }
+    #[test]
+    fn test_upload_limits() {
+        crate::tests::set_config();
+        assert_eq!(upload_max_size(), 4096);
+        assert_eq!(upload_max_field_size(), 64);
+        assert_eq!(upload_spool_threshold(), 16);
+    }
+    #[test]
+    fn test_trusted_proxies() {
+        crate::tests::set_config();
+        assert_eq!(trusted_proxies(), ["10.0.0.0/8", "::1"]);
+    }
+    #[test]
+    fn test_global_middleware() {
+        crate::tests::set_config();
+        assert!(global_middleware().is_empty());
+    }
 }
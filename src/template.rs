@@ -198,7 +198,8 @@ mod tests {
                 .add("error_code", &500)
                 .add("error_message", "Internal Server Error")
                 .add("message", "This is a test.")
-                .add("request", &Request::mock()),
+                .add("request", &Request::mock())
+                .add("raw", &Request::mock().get_raw()),
         );
         assert!(content.contains("Error 500 | Internal Server Error | Kalgan Framework"))
     }